@@ -0,0 +1,157 @@
+//! Sound effects.
+//!
+//! The game only says what happened, a backend decides what that sounds
+//! like: the terminal bell, nothing at all, or (with the `synth` feature)
+//! short tunes synthesized on the fly and played with `aplay`.
+
+use std::io::{self, Write};
+
+/// Something worth hearing about
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sound {
+    /// A trail reached land and the sea behind it filled up
+    TrailClosed,
+    /// A player lost a life
+    Death,
+    /// Enough of the sea is filled to move on
+    LevelClear,
+    /// A power-up gave a player another life
+    ExtraLife,
+}
+
+/// A way of making the sounds
+pub trait Audio {
+    /// What `open` knows this backend by
+    fn name(&self) -> &'static str;
+    /// Start playing the sound, without waiting for it to finish
+    fn play(&mut self, sound: Sound);
+}
+
+/// Names of the available backends, the default first
+pub fn backends() -> Vec<&'static str> {
+    let mut names = vec!["bell"];
+    if cfg!(feature = "synth") {
+        names.push("synth");
+    }
+    names.push("off");
+    names
+}
+
+/// Unknown names give the silent backend
+pub fn open(name: &str) -> Box<dyn Audio> {
+    match name {
+        "bell" => Box::new(Bell),
+        #[cfg(feature = "synth")]
+        "synth" => Box::new(synth::Synth),
+        _ => Box::new(Silent),
+    }
+}
+
+/// Plays nothing
+pub struct Silent;
+
+impl Audio for Silent {
+    fn name(&self) -> &'static str {
+        "off"
+    }
+
+    fn play(&mut self, _sound: Sound) {}
+}
+
+/// Rings the terminal bell
+pub struct Bell;
+
+impl Audio for Bell {
+    fn name(&self) -> &'static str {
+        "bell"
+    }
+
+    fn play(&mut self, sound: Sound) {
+        // There's only one beep, and hearing it on every cut gets old fast
+        if sound == Sound::TrailClosed {
+            return;
+        }
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+    }
+}
+
+#[cfg(feature = "synth")]
+mod synth {
+    use std::f64::consts::PI;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use super::{Audio, Sound};
+
+    const SAMPLE_RATE: u32 = 22050;
+
+    /// Frequency in Hz and length in milliseconds
+    type Note = (f64, u32);
+
+    pub struct Synth;
+
+    impl Audio for Synth {
+        fn name(&self) -> &'static str {
+            "synth"
+        }
+
+        fn play(&mut self, sound: Sound) {
+            let notes: &[Note] = match sound {
+                Sound::TrailClosed => &[(660.0, 40), (880.0, 40)],
+                Sound::Death => &[(440.0, 80), (330.0, 80), (220.0, 160)],
+                Sound::LevelClear => &[(523.0, 80), (659.0, 80), (784.0, 80), (1047.0, 160)],
+                Sound::ExtraLife => &[(880.0, 60), (1175.0, 60), (1568.0, 100)],
+            };
+            let wav = wav(notes);
+
+            // Missing `aplay` just means no sound
+            thread::spawn(move || {
+                let child = Command::new("aplay")
+                    .arg("-q")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn();
+                if let Ok(mut child) = child {
+                    if let Some(mut stdin) = child.stdin.take() {
+                        let _ = stdin.write_all(&wav);
+                    }
+                    let _ = child.wait();
+                }
+            });
+        }
+    }
+
+    /// 8-bit mono PCM, with each note fading out to avoid clicks
+    fn wav(notes: &[Note]) -> Vec<u8> {
+        let mut samples = vec![];
+        for &(frequency, ms) in notes {
+            let count = SAMPLE_RATE * ms / 1000;
+            for i in 0..count {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let fade = 1.0 - i as f64 / count as f64;
+                let value = (2.0 * PI * frequency * t).sin() * fade * 0.5;
+                samples.push((128.0 + value * 127.0) as u8);
+            }
+        }
+
+        let mut wav = vec![];
+        let mut chunk = |bytes: &[u8]| wav.extend_from_slice(bytes);
+        chunk(b"RIFF");
+        chunk(&(36 + samples.len() as u32).to_le_bytes());
+        chunk(b"WAVEfmt ");
+        chunk(&16u32.to_le_bytes());
+        chunk(&1u16.to_le_bytes()); // PCM
+        chunk(&1u16.to_le_bytes()); // mono
+        chunk(&SAMPLE_RATE.to_le_bytes());
+        chunk(&SAMPLE_RATE.to_le_bytes()); // bytes per second
+        chunk(&1u16.to_le_bytes()); // bytes per sample
+        chunk(&8u16.to_le_bytes()); // bits per sample
+        chunk(b"data");
+        chunk(&(samples.len() as u32).to_le_bytes());
+        chunk(&samples);
+        wav
+    }
+}
@@ -0,0 +1,162 @@
+//! The crossterm backend.
+//!
+//! crossterm speaks the kitty keyboard protocol itself, so releases simply
+//! come in as key events of their own kind.
+
+use crossterm::{cursor, event, queue, style, terminal};
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, MouseButton, MouseEventKind};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use capabilities::Capabilities;
+use renderer::Color;
+use super::{Input, Key, Terminal};
+
+/// Whether key releases were asked for, while the terminal is ours. Kept
+/// out here so the panic hook can put things back.
+static SAVED: Mutex<Option<bool>> = Mutex::new(None);
+
+pub struct CrosstermTerminal {
+    stdout: io::Stdout,
+    key_release: bool,
+}
+
+impl CrosstermTerminal {
+    pub fn open(capabilities: &Capabilities) -> io::Result<CrosstermTerminal> {
+        // Dropping it restores the terminal, even if entering fails halfway
+        let mut terminal = CrosstermTerminal {
+            stdout: io::stdout(),
+            key_release: capabilities.key_release,
+        };
+        terminal.enter()?;
+        Ok(terminal)
+    }
+
+    fn enter(&mut self) -> io::Result<()> {
+        let fresh = {
+            let mut saved = SAVED.lock().unwrap();
+            let fresh = saved.is_none();
+            // crossterm skips enabling raw mode when it thinks it already
+            // did, which isn't true anymore if the shell reset the terminal
+            if !fresh {
+                terminal::disable_raw_mode()?;
+            }
+            terminal::enable_raw_mode()?;
+            *saved = Some(self.key_release);
+            fresh
+        };
+
+        queue!(self.stdout,
+               terminal::EnterAlternateScreen,
+               event::EnableMouseCapture,
+               event::EnableFocusChange,
+               terminal::Clear(terminal::ClearType::All),
+               cursor::Hide)?;
+        // The flags are a stack, pushing twice would need popping twice
+        if self.key_release && fresh {
+            let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES |
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+            queue!(self.stdout, event::PushKeyboardEnhancementFlags(flags))?;
+        }
+        self.stdout.flush()
+    }
+}
+
+pub fn restore() {
+    // A panic while holding the lock doesn't change what has to be undone
+    let saved = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(key_release) = saved {
+        let mut stdout = io::stdout();
+        if key_release {
+            let _ = queue!(stdout, event::PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(stdout,
+                       cursor::Show,
+                       event::DisableFocusChange,
+                       event::DisableMouseCapture,
+                       terminal::LeaveAlternateScreen);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Terminal for CrosstermTerminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()> {
+        queue!(self.stdout, cursor::MoveTo(x, y))
+    }
+
+    fn set_colors(&mut self, fg: Color, bg: Color) -> io::Result<()> {
+        queue!(self.stdout,
+               style::SetForegroundColor(convert_color(fg)),
+               style::SetBackgroundColor(convert_color(bg)))
+    }
+
+    fn print(&mut self, c: char) -> io::Result<()> {
+        queue!(self.stdout, style::Print(c))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+
+    fn suspend(&mut self) {
+        restore();
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.enter()
+    }
+}
+
+impl Drop for CrosstermTerminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+fn convert_color(color: Color) -> style::Color {
+    match color {
+        Color::Reset => style::Color::Reset,
+        Color::Rgb(r, g, b) => style::Color::Rgb { r, g, b },
+        c => style::Color::AnsiValue(c.palette_index().unwrap()),
+    }
+}
+
+/// Returns at the end of the input
+pub fn read_input(input: &Input) {
+    loop {
+        let e = match event::read() {
+            Ok(e) => e,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        };
+        match e {
+            event::Event::Key(key) => {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                let converted = match key.code {
+                    KeyCode::Up => Key::Up,
+                    KeyCode::Down => Key::Down,
+                    KeyCode::Left => Key::Left,
+                    KeyCode::Right => Key::Right,
+                    KeyCode::Esc => Key::Esc,
+                    KeyCode::Char(c) if ctrl => Key::Ctrl(c),
+                    KeyCode::Char(c) => Key::Char(c),
+                    _ => continue,
+                };
+                match key.kind {
+                    KeyEventKind::Release => input.release(converted),
+                    KeyEventKind::Press | KeyEventKind::Repeat => input.press(converted),
+                }
+            },
+            event::Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                input.click(mouse.column, mouse.row);
+            },
+            event::Event::FocusLost => input.focus_lost(),
+            _ => {},
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! The terminal library underneath the screen and the keyboard.
+//!
+//! A backend puts the terminal into raw mode on the alternate screen, draws
+//! what `Screen` asks it to and turns keys and clicks into game events.
+//! termion is always there; crossterm comes with the `crossterm` feature and
+//! is then the default. `RETRONIX_BACKEND` picks one by name.
+
+mod termion;
+#[cfg(feature = "crossterm")]
+mod crossterm;
+
+use std::env;
+use std::io;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use capabilities::Capabilities;
+use game::Event;
+use keyboard::HoldTracker;
+use renderer::Color;
+
+/// Output side of a backend, restoring the terminal when dropped
+pub trait Terminal {
+    /// Columns and rows
+    fn size(&self) -> io::Result<(u16, u16)>;
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()>;
+    fn set_colors(&mut self, fg: Color, bg: Color) -> io::Result<()>;
+    fn print(&mut self, c: char) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+    /// Hand the terminal back to the shell, as when dropped
+    fn suspend(&mut self);
+    /// Take the terminal over again, also after the shell reset it
+    fn resume(&mut self) -> io::Result<()>;
+}
+
+/// Keys as far as the game is concerned, whichever backend read them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Esc,
+    Char(char),
+    Ctrl(char),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Backend {
+    Termion,
+    #[cfg(feature = "crossterm")]
+    Crossterm,
+}
+
+impl Backend {
+    /// Names of the compiled in backends, the default first
+    pub fn names() -> Vec<&'static str> {
+        let mut names = vec![];
+        if cfg!(feature = "crossterm") {
+            names.push("crossterm");
+        }
+        names.push("termion");
+        names
+    }
+
+    pub fn find(name: &str) -> Option<Backend> {
+        match name {
+            "termion" => Some(Backend::Termion),
+            #[cfg(feature = "crossterm")]
+            "crossterm" => Some(Backend::Crossterm),
+            _ => None,
+        }
+    }
+
+    /// The one named by `RETRONIX_BACKEND`, falling back to the default
+    pub fn from_env() -> Backend {
+        env::var("RETRONIX_BACKEND").ok()
+            .and_then(|name| Backend::find(&name))
+            .unwrap_or_else(|| Backend::find(Backend::names()[0]).unwrap())
+    }
+
+    /// Take over the terminal until the returned value is dropped
+    pub fn open(self, capabilities: &Capabilities) -> io::Result<Box<dyn Terminal>> {
+        Ok(match self {
+            Backend::Termion => Box::new(termion::TermionTerminal::open(capabilities)?),
+            #[cfg(feature = "crossterm")]
+            Backend::Crossterm => Box::new(crossterm::CrosstermTerminal::open(capabilities)?),
+        })
+    }
+
+    /// Read keys and clicks on a separate thread, sending `Quit` once the
+    /// input ends
+    pub fn spawn_input(self, tx: mpsc::Sender<Event>, hold: Arc<Mutex<HoldTracker>>) {
+        let input = Input { tx, hold };
+        thread::spawn(move || {
+            match self {
+                Backend::Termion => termion::read_input(&input),
+                #[cfg(feature = "crossterm")]
+                Backend::Crossterm => crossterm::read_input(&input),
+            }
+            input.send(Event::Quit);
+        });
+    }
+}
+
+/// Put the terminal back the way it was, whichever backend has it. Safe to
+/// call at any time, which the panic hook relies on.
+pub fn restore() {
+    termion::restore();
+    #[cfg(feature = "crossterm")]
+    crossterm::restore();
+}
+
+/// Where the backends send what they read
+#[derive(Clone)]
+struct Input {
+    tx: mpsc::Sender<Event>,
+    hold: Arc<Mutex<HoldTracker>>,
+}
+
+impl Input {
+    fn send(&self, event: Event) {
+        // Nobody listening means the game is over and about to exit
+        let _ = self.tx.send(event);
+    }
+
+    fn press(&self, key: Key) {
+        if let Some(event) = key_event(key) {
+            self.hold.lock().unwrap().press(event);
+            self.send(event);
+        }
+    }
+
+    fn release(&self, key: Key) {
+        let stop = key_event(key).and_then(|e| self.hold.lock().unwrap().release(e));
+        if let Some(stop) = stop {
+            self.send(stop);
+        }
+    }
+
+    /// Coordinates start at 0
+    fn click(&self, x: u16, y: u16) {
+        self.send(Event::Click(x, y));
+    }
+
+    /// Nobody is looking, so the game shouldn't go on without them
+    fn focus_lost(&self) {
+        self.send(Event::Pause);
+    }
+}
+
+fn key_event(key: Key) -> Option<Event> {
+    match key {
+        Key::Up        => Some(Event::Up),
+        Key::Down      => Some(Event::Down),
+        Key::Left      => Some(Event::Left),
+        Key::Right     => Some(Event::Right),
+        Key::Char('w') => Some(Event::Up2),
+        Key::Char('s') => Some(Event::Down2),
+        Key::Char('a') => Some(Event::Left2),
+        Key::Char('d') => Some(Event::Right2),
+        Key::Esc       => Some(Event::Back),
+        Key::Char(' ') => Some(Event::Select),
+        Key::Ctrl('c') => Some(Event::Quit),
+        Key::Ctrl('z') => Some(Event::Suspend),
+        _ => None,
+    }
+}
@@ -0,0 +1,408 @@
+//! The termion backend.
+//!
+//! termion knows neither the kitty keyboard protocol nor focus reports, so
+//! `ReportReader` picks those out of the input first and hands termion the
+//! legacy bytes.
+
+use libc;
+use termion;
+use termion::color;
+use termion::event::{self as term, MouseButton, MouseEvent};
+use termion::input::TermRead;
+use termion::screen::{ToAlternateScreen, ToMainScreen};
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::Mutex;
+
+use capabilities::Capabilities;
+use renderer::Color;
+use super::{Input, Key, Terminal};
+
+/// Asks for release events and every key as an escape code
+const KITTY_ENABLE: &str = "\x1b[>10u";
+const KITTY_DISABLE: &str = "\x1b[<u";
+
+/// The same reporting termion's `MouseTerminal` asks for
+const MOUSE_ENABLE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const MOUSE_DISABLE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+/// Asks for `CSI I` and `CSI O` when the window gains and loses focus
+const FOCUS_ENABLE: &str = "\x1b[?1004h";
+const FOCUS_DISABLE: &str = "\x1b[?1004l";
+
+/// Terminal settings from before raw mode, and whether key releases were
+/// asked for. Kept out here so the panic hook can put things back.
+static SAVED: Mutex<Option<(libc::termios, bool)>> = Mutex::new(None);
+
+pub struct TermionTerminal {
+    stdout: io::Stdout,
+    key_release: bool,
+}
+
+impl TermionTerminal {
+    pub fn open(capabilities: &Capabilities) -> io::Result<TermionTerminal> {
+        // Dropping it restores the terminal, even if entering fails halfway
+        let mut terminal = TermionTerminal {
+            stdout: io::stdout(),
+            key_release: capabilities.key_release,
+        };
+        terminal.enter()?;
+        Ok(terminal)
+    }
+
+    fn enter(&mut self) -> io::Result<()> {
+        // Like termion's raw mode, which can't be undone from a panic hook.
+        // Settings from before the first time are the ones to go back to.
+        let fresh = {
+            let mut saved = SAVED.lock().unwrap();
+            let fresh = saved.is_none();
+            let ios = match *saved {
+                Some((ios, _)) => ios,
+                None => {
+                    let mut ios: libc::termios = unsafe { mem::zeroed() };
+                    check(unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut ios) })?;
+                    ios
+                },
+            };
+            let mut raw = ios;
+            unsafe { libc::cfmakeraw(&mut raw) };
+            check(unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) })?;
+            *saved = Some((ios, self.key_release));
+            fresh
+        };
+
+        write!(self.stdout, "{}{}{}{}{}",
+               ToAlternateScreen,
+               MOUSE_ENABLE,
+               FOCUS_ENABLE,
+               termion::clear::All,
+               termion::cursor::Hide)?;
+        // The flags are a stack, pushing twice would need popping twice
+        if self.key_release && fresh {
+            write!(self.stdout, "{}", KITTY_ENABLE)?;
+        }
+        self.stdout.flush()
+    }
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result == -1 { Err(io::Error::last_os_error()) } else { Ok(()) }
+}
+
+pub fn restore() {
+    // A panic while holding the lock doesn't make the settings any less valid
+    let saved = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some((ios, key_release)) = saved {
+        let mut stdout = io::stdout();
+        if key_release {
+            let _ = write!(stdout, "{}", KITTY_DISABLE);
+        }
+        let _ = write!(stdout, "{}{}{}{}",
+                       FOCUS_DISABLE,
+                       MOUSE_DISABLE,
+                       termion::cursor::Show,
+                       ToMainScreen);
+        let _ = stdout.flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &ios) };
+    }
+}
+
+impl Terminal for TermionTerminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        termion::terminal_size()
+    }
+
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()> {
+        // Terminal coordinates start at 1
+        write!(self.stdout, "{}", termion::cursor::Goto(x + 1, y + 1))
+    }
+
+    fn set_colors(&mut self, fg: Color, bg: Color) -> io::Result<()> {
+        write!(self.stdout, "{}{}", Fg(fg), Bg(bg))
+    }
+
+    fn print(&mut self, c: char) -> io::Result<()> {
+        write!(self.stdout, "{}", c)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+
+    fn suspend(&mut self) {
+        restore();
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.enter()
+    }
+}
+
+impl Drop for TermionTerminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+struct Fg(Color);
+struct Bg(Color);
+
+impl fmt::Display for Fg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Color::Reset => write!(f, "{}", color::Fg(color::Reset)),
+            Color::Rgb(r, g, b) => write!(f, "{}", color::Fg(color::Rgb(r, g, b))),
+            c => write!(f, "{}", color::Fg(color::AnsiValue(c.palette_index().unwrap()))),
+        }
+    }
+}
+
+impl fmt::Display for Bg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Color::Reset => write!(f, "{}", color::Bg(color::Reset)),
+            Color::Rgb(r, g, b) => write!(f, "{}", color::Bg(color::Rgb(r, g, b))),
+            c => write!(f, "{}", color::Bg(color::AnsiValue(c.palette_index().unwrap()))),
+        }
+    }
+}
+
+/// Returns at the end of the input
+pub fn read_input(input: &Input) {
+    let reports = input.clone();
+    let stdin = ReportReader::new(io::stdin(), move |report| match report {
+        Report::Released(key) => reports.release(key),
+        Report::FocusLost => reports.focus_lost(),
+    });
+
+    for e in stdin.events() {
+        let e = match e {
+            Ok(e) => e,
+            // termion gives up on sequences it doesn't know, which is fine
+            Err(ref e) if e.raw_os_error().is_none() || e.kind() == io::ErrorKind::Interrupted => {
+                continue
+            },
+            // A terminal that went away gives errors rather than the end of input
+            Err(_) => return,
+        };
+        match e {
+            term::Event::Key(key) => {
+                if let Some(key) = convert_key(key) {
+                    input.press(key);
+                }
+            },
+            term::Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => input.click(x - 1, y - 1),
+            _ => {},
+        }
+    }
+}
+
+fn convert_key(key: term::Key) -> Option<Key> {
+    match key {
+        term::Key::Up => Some(Key::Up),
+        term::Key::Down => Some(Key::Down),
+        term::Key::Left => Some(Key::Left),
+        term::Key::Right => Some(Key::Right),
+        term::Key::Esc => Some(Key::Esc),
+        term::Key::Char(c) => Some(Key::Char(c)),
+        term::Key::Ctrl(c) => Some(Key::Ctrl(c)),
+        _ => None,
+    }
+}
+
+/// What termion wouldn't have understood
+#[derive(PartialEq, Debug)]
+enum Report {
+    Released(Key),
+    FocusLost,
+}
+
+/// Rewrites kitty protocol key events into the legacy bytes termion
+/// understands, passing releases and focus changes to `on_report` instead
+struct ReportReader<R: Read, F: FnMut(Report)> {
+    inner: R,
+    on_report: F,
+    /// Bytes read but not looked at yet, possibly an unfinished sequence
+    raw: Vec<u8>,
+    /// Bytes ready to be handed out
+    ready: Vec<u8>,
+}
+
+impl<R: Read, F: FnMut(Report)> ReportReader<R, F> {
+    fn new(inner: R, on_report: F) -> ReportReader<R, F> {
+        ReportReader {
+            inner,
+            on_report,
+            raw: vec![],
+            ready: vec![],
+        }
+    }
+
+    fn scan(&mut self) {
+        let mut i = 0;
+        while i < self.raw.len() {
+            if self.raw[i..].starts_with(b"\x1b[") {
+                let end = match self.raw[i + 2..].iter().position(|b| (0x40..0x7f).contains(b)) {
+                    Some(len) => i + 2 + len,
+                    // Wait for the rest of the sequence
+                    None => break,
+                };
+
+                let params = String::from_utf8_lossy(&self.raw[i + 2..end]).into_owned();
+                match self.translate(&params, self.raw[end]) {
+                    Some(bytes) => self.ready.extend_from_slice(&bytes),
+                    None => self.ready.extend_from_slice(&self.raw[i..end + 1]),
+                }
+                i = end + 1;
+                continue;
+            }
+
+            self.ready.push(self.raw[i]);
+            i += 1;
+        }
+        self.raw.drain(..i);
+    }
+
+    /// Returns None for sequences that aren't kitty key events or focus
+    /// changes
+    fn translate(&mut self, params: &str, last: u8) -> Option<Vec<u8>> {
+        match (params, last) {
+            ("", b'I') => return Some(vec![]),
+            ("", b'O') => {
+                (self.on_report)(Report::FocusLost);
+                return Some(vec![]);
+            },
+            _ => {},
+        }
+
+        // CSI code[:alternates] ; modifiers[:event type] [; text] u
+        // or CSI 1 ; modifiers[:event type] A-D for the arrows
+        let fields: Vec<&str> = params.split(';').collect();
+        let modifiers: Vec<&str> = fields.get(1).map_or(vec![], |f| f.split(':').collect());
+        let ctrl = modifiers.first()
+            .and_then(|m| m.parse::<u8>().ok())
+            .is_some_and(|m| m.saturating_sub(1) & 4 != 0);
+        let released = modifiers.get(1) == Some(&"3");
+
+        let (key, legacy) = match last {
+            b'A' => (Key::Up, b"\x1b[A".to_vec()),
+            b'B' => (Key::Down, b"\x1b[B".to_vec()),
+            b'C' => (Key::Right, b"\x1b[C".to_vec()),
+            b'D' => (Key::Left, b"\x1b[D".to_vec()),
+            b'u' => {
+                let code: u32 = fields[0].split(':').next()?.parse().ok()?;
+                let c = ::std::char::from_u32(code)?;
+                match c {
+                    '\x1b' => (Key::Esc, vec![0x1b]),
+                    c if ctrl && c.is_ascii_alphabetic() => (Key::Ctrl(c), vec![c as u8 & 0x1f]),
+                    c => (Key::Char(c), c.to_string().into_bytes()),
+                }
+            },
+            _ => return None,
+        };
+
+        // Arrows without an event type are plain legacy sequences
+        if last != b'u' && modifiers.len() < 2 {
+            return None;
+        }
+
+        if released {
+            (self.on_report)(Report::Released(key));
+            return Some(vec![]);
+        }
+        Some(legacy)
+    }
+}
+
+impl<R: Read, F: FnMut(Report)> Read for ReportReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.ready.is_empty() {
+            let mut chunk = [0u8; 256];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                // Whatever was left can't be finished anymore
+                self.ready.append(&mut self.raw);
+                if self.ready.is_empty() {
+                    return Ok(0);
+                }
+                break;
+            }
+            self.raw.extend_from_slice(&chunk[..n]);
+            self.scan();
+        }
+
+        let n = cmp::min(buf.len(), self.ready.len());
+        buf[..n].copy_from_slice(&self.ready[..n]);
+        self.ready.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{self, Cursor, Read};
+    use backend::Key;
+    use super::{Report, ReportReader};
+
+    /// Hands out the input in the given pieces, like a slow terminal
+    struct Pieces(VecDeque<&'static [u8]>);
+
+    impl Read for Pieces {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let piece = self.0.pop_front().unwrap_or(&[]);
+            buf[..piece.len()].copy_from_slice(piece);
+            Ok(piece.len())
+        }
+    }
+
+    /// The bytes termion gets and the reports made on the way
+    fn read<R: Read>(inner: R) -> (Vec<u8>, Vec<Report>) {
+        let mut reports = vec![];
+        let mut bytes = vec![];
+        ReportReader::new(inner, |report| reports.push(report))
+            .read_to_end(&mut bytes)
+            .unwrap();
+        (bytes, reports)
+    }
+
+    fn translate(input: &str) -> (Vec<u8>, Vec<Report>) {
+        read(Cursor::new(input.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn presses_and_repeats_become_legacy_keys() {
+        assert_eq!(translate("\x1b[97u"), (b"a".to_vec(), vec![]));
+        assert_eq!(translate("\x1b[97;1:1u\x1b[97;1:2u"), (b"aa".to_vec(), vec![]));
+        assert_eq!(translate("\x1b[1;1:1A\x1b[1;1:2A"), (b"\x1b[A\x1b[A".to_vec(), vec![]));
+        assert_eq!(translate("\x1b[27u"), (b"\x1b".to_vec(), vec![]));
+        assert_eq!(translate("\x1b[99;5u"), (vec![0x03], vec![]));
+    }
+
+    #[test]
+    fn releases_are_reported_instead() {
+        assert_eq!(translate("\x1b[119;1:3u"), (vec![], vec![Report::Released(Key::Char('w'))]));
+        assert_eq!(translate("\x1b[1;1:3D"), (vec![], vec![Report::Released(Key::Left)]));
+        assert_eq!(translate("\x1b[1;1:1B\x1b[1;1:3B"),
+                   (b"\x1b[B".to_vec(), vec![Report::Released(Key::Down)]));
+    }
+
+    #[test]
+    fn focus_changes_are_picked_out() {
+        assert_eq!(translate("\x1b[Ix\x1b[O"), (b"x".to_vec(), vec![Report::FocusLost]));
+    }
+
+    #[test]
+    fn other_input_goes_through_untouched() {
+        let input = "q\x1b[A\x1b[<0;12;5M\x1b[1;5C";
+        assert_eq!(translate(input), (input.as_bytes().to_vec(), vec![]));
+    }
+
+    #[test]
+    fn sequences_split_across_reads_are_put_together() {
+        let pieces: VecDeque<&[u8]> = vec![&b"\x1b[100"[..], b";1:1u\x1b[", b"100;1:", b"3u"].into();
+        assert_eq!(read(Pieces(pieces)), (b"d".to_vec(), vec![Report::Released(Key::Char('d'))]));
+    }
+}
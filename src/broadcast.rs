@@ -0,0 +1,255 @@
+//! Streaming the game to read-only spectators over TCP.
+//!
+//! The protocol is line based. After connecting, a spectator receives
+//! `RETRONIX-WATCH <version> <width> <height>`, followed by a stream of
+//! changed cells (`C <x> <y> <fg> <bg> <char code>`), each frame terminated
+//! with `F`. Newly connected spectators get the whole frame first.
+//!
+//! Colours are sent as `-` for the terminal default, `n<index>` for one of
+//! the 16 named colours, `a<index>` for the 256-colour palette and
+//! `#rrggbb` for truecolor.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time;
+
+use renderer::{Renderer, Surface, Color};
+
+pub const PROTOCOL_VERSION: u32 = 2;
+
+type Cell = (char, Color, Color);
+
+const BLANK: Cell = (' ', Color::White, Color::Reset);
+
+/// Renders to the wrapped surface and forwards every changed cell to spectators
+pub struct Broadcaster<S: Surface> {
+    inner: S,
+    frame: Vec<Vec<Cell>>,
+    sent: Vec<Vec<Cell>>,
+    spectators: Vec<TcpStream>,
+    joining: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl<S: Surface> Broadcaster<S> {
+    pub fn bind(port: u16, inner: S, width: usize, height: usize) -> io::Result<Broadcaster<S>> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let joining = Arc::new(Mutex::new(vec![]));
+
+        let queue = joining.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+
+                // A stalled spectator must not hold up the game
+                let hello = stream.set_write_timeout(Some(time::Duration::from_millis(200)))
+                    .and_then(|_| writeln!(stream, "RETRONIX-WATCH {} {} {}",
+                                           PROTOCOL_VERSION, width, height));
+                if hello.is_ok() {
+                    queue.lock().unwrap().push(stream);
+                }
+            }
+        });
+
+        Ok(Broadcaster {
+            inner,
+            frame: vec![vec![BLANK; width]; height],
+            sent: vec![vec![BLANK; width]; height],
+            spectators: vec![],
+            joining,
+        })
+    }
+
+    fn send(&mut self) {
+        let mut diff = String::new();
+        for (y, (row, sent_row)) in self.frame.iter().zip(self.sent.iter()).enumerate() {
+            for (x, (cell, sent)) in row.iter().zip(sent_row.iter()).enumerate() {
+                if cell != sent {
+                    push_cell(&mut diff, x, y, cell);
+                }
+            }
+        }
+        diff.push_str("F\n");
+
+        let mut joining = self.joining.lock().unwrap();
+        if !joining.is_empty() {
+            let mut full = String::new();
+            for (y, row) in self.frame.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    push_cell(&mut full, x, y, cell);
+                }
+            }
+            full.push_str("F\n");
+
+            for mut stream in joining.drain(..) {
+                if stream.write_all(full.as_bytes()).is_ok() {
+                    self.spectators.push(stream);
+                }
+            }
+        }
+
+        self.spectators.retain(|mut stream| stream.write_all(diff.as_bytes()).is_ok());
+        self.sent.clone_from(&self.frame);
+    }
+}
+
+impl<S: Surface> Renderer for Broadcaster<S> {
+    fn put_cell(&mut self, x: u16, y: u16, c: char, fg: Color, bg: Color) {
+        self.inner.put_cell(x, y, c, fg, bg);
+        if let Some(cell) = self.frame.get_mut(y as usize).and_then(|row| row.get_mut(x as usize)) {
+            *cell = (c, fg, bg);
+        }
+    }
+}
+
+impl<S: Surface> Surface for Broadcaster<S> {
+    fn clear(&mut self) {
+        self.inner.clear();
+        for row in self.frame.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = BLANK;
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Spectators come and go, only the local screen can fail
+        self.send();
+        self.inner.flush()
+    }
+
+    fn suspend(&mut self) {
+        self.inner.suspend();
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.inner.resume()
+    }
+}
+
+/// Connection to a game being broadcast
+pub struct Viewer {
+    reader: BufReader<TcpStream>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A single cell update: position, character, foreground and background
+pub type CellUpdate = (u16, u16, char, Color, Color);
+
+/// Cells that changed in a single frame
+pub type Frame = Vec<CellUpdate>;
+
+impl Viewer {
+    pub fn connect(address: &str) -> io::Result<Viewer> {
+        let mut reader = BufReader::new(TcpStream::connect(address)?);
+
+        let mut hello = String::new();
+        reader.read_line(&mut hello)?;
+        let parts: Vec<&str> = hello.split_whitespace().collect();
+        if parts.len() != 4 || parts[0] != "RETRONIX-WATCH" {
+            return Err(protocol_error(format!("unexpected handshake: {}", hello.trim_end())));
+        }
+        if parts[1] != PROTOCOL_VERSION.to_string() {
+            return Err(protocol_error(format!("unsupported version: {}", parts[1])));
+        }
+
+        Ok(Viewer {
+            reader,
+            width: parse(parts[2])?,
+            height: parse(parts[3])?,
+        })
+    }
+
+    /// Read frames on a separate thread until the connection drops
+    pub fn spawn(self, tx: mpsc::Sender<io::Result<Frame>>) {
+        let (width, height) = (self.width as u16, self.height as u16);
+        thread::spawn(move || {
+            let mut frame = vec![];
+            for line in self.reader.lines() {
+                let result = line.and_then(|line| parse_line(&line));
+                match result {
+                    Ok(Some(cell)) => {
+                        if cell.0 < width && cell.1 < height {
+                            frame.push(cell);
+                        }
+                    },
+                    Ok(None) => {
+                        if tx.send(Ok(frame)).is_err() {
+                            return;
+                        }
+                        frame = vec![];
+                    },
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                        return;
+                    },
+                }
+            }
+            let _ = tx.send(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "broadcast ended")));
+        });
+    }
+}
+
+fn push_cell(out: &mut String, x: usize, y: usize, &(c, fg, bg): &Cell) {
+    out.push_str(&format!("C {} {} {} {} {}\n",
+                          x, y, color_code(fg), color_code(bg), c as u32));
+}
+
+/// Returns None at the end of a frame
+fn parse_line(line: &str) -> io::Result<Option<CellUpdate>> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    match parts.as_slice() {
+        ["F"] => Ok(None),
+        ["C", x, y, fg, bg, c] => {
+            let c = ::std::char::from_u32(parse(c)?)
+                .ok_or_else(|| protocol_error(format!("invalid char: {}", c)))?;
+            Ok(Some((parse(x)?, parse(y)?, c, parse_color(fg)?, parse_color(bg)?)))
+        },
+        _ => Err(protocol_error(format!("unexpected message: {}", line))),
+    }
+}
+
+fn color_code(color: Color) -> String {
+    match color {
+        Color::Reset => "-".to_string(),
+        Color::Ansi(index) => format!("a{}", index),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        named => format!("n{}", named.palette_index().unwrap()),
+    }
+}
+
+fn parse_color(code: &str) -> io::Result<Color> {
+    let invalid = || protocol_error(format!("invalid color: {}", code));
+
+    if code == "-" {
+        return Ok(Color::Reset);
+    }
+    if let Some(index) = code.strip_prefix('a') {
+        return Ok(Color::Ansi(parse(index)?));
+    }
+    if let Some(index) = code.strip_prefix('n') {
+        let index: usize = parse(index)?;
+        return Color::NAMED.get(index).cloned().ok_or_else(invalid);
+    }
+    if let Some(hex) = code.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        return Ok(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    Err(invalid())
+}
+
+fn parse<T: ::std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse().map_err(|_| protocol_error(format!("invalid number: {}", s)))
+}
+
+fn protocol_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,47 @@
+//! What the terminal can display, guessed from the environment.
+
+use std::env;
+
+pub struct Capabilities {
+    /// The locale uses UTF-8, so box drawing and block characters are safe
+    pub unicode: bool,
+    /// Colour escape sequences are understood and wanted
+    pub color: bool,
+    /// The kitty keyboard protocol can tell when keys are let go
+    pub key_release: bool,
+}
+
+/// Terminals known to show neither colour nor anything beyond ASCII
+const PLAIN_TERMINALS: [&str; 5] = ["dumb", "vt52", "vt100", "vt102", "vt220"];
+
+/// Terminals known to speak the kitty keyboard protocol, by `TERM_PROGRAM`
+const KITTY_KEYBOARD_PROGRAMS: [&str; 3] = ["WezTerm", "ghostty", "foot"];
+
+pub fn detect() -> Capabilities {
+    let term = env::var("TERM").unwrap_or_default();
+    let plain = term.is_empty() || PLAIN_TERMINALS.contains(&term.as_str());
+
+    // See https://no-color.org
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let kitty = term == "xterm-kitty" || env::var_os("KITTY_WINDOW_ID").is_some() ||
+        KITTY_KEYBOARD_PROGRAMS.contains(&program.as_str());
+
+    Capabilities {
+        unicode: !plain && locale_is_utf8(),
+        color: !plain && !no_color,
+        key_release: kitty,
+    }
+}
+
+/// The first of LC_ALL, LC_CTYPE and LANG that is set decides the charset
+fn locale_is_utf8() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    locale.contains("utf-8") || locale.contains("utf8")
+}
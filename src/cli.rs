@@ -0,0 +1,221 @@
+//! Command line arguments.
+
+use std::path::PathBuf;
+
+use error::{Error, Result};
+use game::{Mode, PlayOptions};
+use theme::Theme;
+
+pub const USAGE: &str = "Usage: retronix [play] [OPTIONS]
+       retronix replay FILE
+       retronix scores
+       retronix edit LEVEL [--size WxH]
+       retronix bench [OPTIONS]
+       retronix --host PORT [--coop|--versus]
+       retronix --join HOST:PORT
+       retronix --watch HOST:PORT
+       retronix --help | --version
+
+Options:
+  --level N         start on level N (1)
+  --lives N         start with N lives (3)
+  --seed N          play the same boards every time
+  --size WxH        size of the board (80x25)
+  --speed X         pace of the game, from 0.25 to 4 (1)
+  --theme NAME      one of the themes under OPTIONS
+  --record FILE     save the game for `retronix replay`
+  --broadcast PORT  let others watch with --watch";
+
+/// Range of `--speed`
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 4.0;
+
+pub enum Command {
+    Play(PlayArgs),
+    Replay(PathBuf),
+    Scores,
+    /// Draw the layout of the level in the options
+    Edit(PlayArgs),
+    /// Time the game running flat out, without a terminal
+    Bench(PlayArgs),
+    Host(u16, Mode),
+    Join(String),
+    Watch(String),
+    Help,
+    Version,
+}
+
+/// How to play a local game
+pub struct PlayArgs {
+    pub options: PlayOptions,
+    /// Multiplies the number of ticks per second
+    pub speed: f64,
+    /// None to pick one that suits the terminal
+    pub theme: Option<Theme>,
+    pub record: Option<PathBuf>,
+    pub broadcast: Option<u16>,
+}
+
+impl Default for PlayArgs {
+    fn default() -> PlayArgs {
+        PlayArgs {
+            options: PlayOptions::default(),
+            speed: 1.0,
+            theme: None,
+            record: None,
+            broadcast: None,
+        }
+    }
+}
+
+/// Without the program name
+pub fn parse(args: &[&str]) -> Result<Command> {
+    match args {
+        ["--help"] | ["-h"] | ["help"] => Ok(Command::Help),
+        ["--version"] | ["-V"] => Ok(Command::Version),
+        ["replay", file] => Ok(Command::Replay(PathBuf::from(file))),
+        ["scores"] => Ok(Command::Scores),
+        ["edit", level, rest @ ..] => {
+            let mut play = parse_play(rest)?;
+            play.options.level = parse_number("LEVEL", level)?;
+            play.options.check().map_err(Error::Usage)?;
+            Ok(Command::Edit(play))
+        },
+        ["bench", rest @ ..] => Ok(Command::Bench(parse_play(rest)?)),
+        ["play", rest @ ..] => Ok(Command::Play(parse_play(rest)?)),
+        ["--host", port] | ["--host", port, "--coop"] => Ok(Command::Host(parse_port(port)?, Mode::Coop)),
+        ["--host", port, "--versus"] => Ok(Command::Host(parse_port(port)?, Mode::Versus)),
+        ["--join", address] => Ok(Command::Join(address.to_string())),
+        ["--watch", address] => Ok(Command::Watch(address.to_string())),
+        // Options alone are enough to play
+        [first, ..] if !first.starts_with("--") => Err(Error::Usage(USAGE.to_string())),
+        rest => Ok(Command::Play(parse_play(rest)?)),
+    }
+}
+
+fn parse_play(mut args: &[&str]) -> Result<PlayArgs> {
+    let mut play = PlayArgs::default();
+
+    while let [option, rest @ ..] = args {
+        let value = || rest.first().ok_or_else(|| Error::Usage(format!("Missing value for {}", option)));
+
+        match *option {
+            "--level" => play.options.level = parse_number(option, value()?)?,
+            "--lives" => play.options.lives = parse_number(option, value()?)?,
+            "--seed" => play.options.seed = Some(parse_number(option, value()?)?),
+            "--size" => {
+                let value = value()?;
+                let (width, height) = parse_size(value)
+                    .ok_or_else(|| Error::Usage(format!("Invalid size: {}, expected WIDTHxHEIGHT", value)))?;
+                play.options.width = width;
+                play.options.height = height;
+            },
+            "--speed" => {
+                let speed: f64 = parse_number(option, value()?)?;
+                if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+                    let range = format!("between {} and {}", MIN_SPEED, MAX_SPEED);
+                    return Err(Error::Usage(format!("The speed must be {}", range)));
+                }
+                play.speed = speed;
+            },
+            "--theme" => {
+                let value = value()?;
+                let theme = Theme::find(value).ok_or_else(|| {
+                    let names: Vec<String> = Theme::available().into_iter().map(|t| t.name).collect();
+                    Error::Usage(format!("No theme called {}, try one of: {}", value, names.join(", ")))
+                })?;
+                play.theme = Some(theme);
+            },
+            "--record" => play.record = Some(PathBuf::from(value()?)),
+            "--broadcast" => play.broadcast = Some(parse_port(value()?)?),
+            _ => return Err(Error::Usage(format!("Unknown option: {}", option))),
+        }
+        args = &rest[1..];
+    }
+
+    play.options.check().map_err(Error::Usage)?;
+    Ok(play)
+}
+
+fn parse_port(port: &str) -> Result<u16> {
+    port.parse().map_err(|_| Error::Usage(format!("Invalid port: {}", port)))
+}
+
+fn parse_number<T: ::std::str::FromStr>(option: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| Error::Usage(format!("Invalid value for {}: {}", option, value)))
+}
+
+/// `WIDTHxHEIGHT`, like `80x25`
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use error::Error;
+    use game::Mode;
+    use theme::Theme;
+    use super::{parse, Command, MAX_SPEED};
+
+    fn usage_error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(Error::Usage(message)) => message,
+            _ => panic!("{:?} should be a usage error", args),
+        }
+    }
+
+    #[test]
+    fn options_alone_start_a_game() {
+        let args = ["--level", "3", "--lives", "5", "--seed", "42", "--size", "120x40", "--speed", "1.5"];
+        let play = match parse(&args) {
+            Ok(Command::Play(play)) => play,
+            _ => panic!("should play"),
+        };
+        assert_eq!((play.options.level, play.options.lives, play.options.seed), (3, 5, Some(42)));
+        assert_eq!((play.options.width, play.options.height), (120, 40));
+        assert_eq!(play.speed, 1.5);
+        assert!(play.theme.is_none() && play.record.is_none() && play.broadcast.is_none());
+
+        let play = match parse(&["play", "--theme", "ascii", "--record", "game.replay"]) {
+            Ok(Command::Play(play)) => play,
+            _ => panic!("should play"),
+        };
+        assert_eq!(play.theme.map(|theme| theme.name), Some(Theme::ascii().name));
+        assert_eq!(play.record.as_deref(), Some(Path::new("game.replay")));
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse(&[]), Ok(Command::Play(_))));
+        assert!(matches!(parse(&["--help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["-V"]), Ok(Command::Version)));
+        assert!(matches!(parse(&["scores"]), Ok(Command::Scores)));
+        assert!(matches!(parse(&["replay", "game.replay"]),
+                         Ok(Command::Replay(ref path)) if path == Path::new("game.replay")));
+        assert!(matches!(parse(&["edit", "7"]), Ok(Command::Edit(ref play)) if play.options.level == 7));
+        assert!(matches!(parse(&["bench", "--seed", "1"]),
+                         Ok(Command::Bench(ref play)) if play.options.seed == Some(1)));
+        assert!(matches!(parse(&["--host", "7777"]), Ok(Command::Host(7777, Mode::Coop))));
+        assert!(matches!(parse(&["--host", "7777", "--versus"]), Ok(Command::Host(7777, Mode::Versus))));
+        assert!(matches!(parse(&["--join", "localhost:7777"]), Ok(Command::Join(ref a)) if a == "localhost:7777"));
+        assert!(matches!(parse(&["--watch", "localhost:7700"]), Ok(Command::Watch(ref a)) if a == "localhost:7700"));
+    }
+
+    #[test]
+    fn mistakes_are_usage_errors() {
+        assert_eq!(usage_error(&["--level"]), "Missing value for --level");
+        assert_eq!(usage_error(&["--lives", "many"]), "Invalid value for --lives: many");
+        assert_eq!(usage_error(&["--size", "80"]), "Invalid size: 80, expected WIDTHxHEIGHT");
+        assert_eq!(usage_error(&["--size", "10x10"]), "The board must be between 30x14 and 250x100");
+        assert_eq!(usage_error(&["--level", "0"]), "The level must be between 1 and 99");
+        assert!(usage_error(&["--speed", &(MAX_SPEED * 2.0).to_string()]).starts_with("The speed must be"));
+        assert!(usage_error(&["--theme", "nonexistent"]).starts_with("No theme called nonexistent"));
+        assert_eq!(usage_error(&["--host", "port"]), "Invalid port: port");
+        assert_eq!(usage_error(&["--fast"]), "Unknown option: --fast");
+        assert!(usage_error(&["dance"]).starts_with("Usage:"));
+    }
+}
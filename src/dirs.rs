@@ -0,0 +1,23 @@
+//! Where the game keeps its files, following the XDG base directories.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// `$XDG_DATA_HOME/retronix`, or `~/.local/share/retronix`: scores and
+/// level layouts
+pub fn data_dir() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+/// `$XDG_CONFIG_HOME/retronix`, or `~/.config/retronix`: themes
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", &[".config"])
+}
+
+fn base_dir(variable: &str, under_home: &[&str]) -> Option<PathBuf> {
+    let base = env::var_os(variable).map(PathBuf::from).or_else(|| {
+        let home = env::var_os("HOME")?;
+        Some(under_home.iter().fold(Path::new(&home).to_path_buf(), |path, dir| path.join(dir)))
+    })?;
+    Some(base.join("retronix"))
+}
@@ -0,0 +1,66 @@
+//! Errors that end the game, reported once the terminal is restored.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+/// Why the game couldn't go on
+#[derive(Debug)]
+pub enum Error {
+    /// The command line didn't make sense
+    Usage(String),
+    /// What was being done, and the error that stopped it
+    Io(String, io::Error),
+}
+
+/// Result with the game's `Error`
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    /// What the process should exit with
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => 2,
+            Error::Io(..) => 1,
+        }
+    }
+
+    /// Whoever was reading the output went away, nothing worth reporting
+    pub fn is_broken_pipe(&self) -> bool {
+        match *self {
+            Error::Io(_, ref e) => e.kind() == io::ErrorKind::BrokenPipe,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref message) => write!(f, "{}", message),
+            Error::Io(ref context, ref e) => write!(f, "{}: {}", context, e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Usage(_) => None,
+            Error::Io(_, ref e) => Some(e),
+        }
+    }
+}
+
+/// Adds what was being done to an I/O error
+pub trait Context<T> {
+    /// Wrap the error with `what` was being done, like "Cannot draw the game"
+    fn context<S: Into<String>>(self, what: S) -> Result<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context<S: Into<String>>(self, what: S) -> Result<T> {
+        self.map_err(|e| Error::Io(what.into(), e))
+    }
+}
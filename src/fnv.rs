@@ -0,0 +1,29 @@
+//! FNV-1a hashing, which unlike the standard library's hasher gives the
+//! same result on every platform and with every compiler release.
+
+const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+/// Fed fixed-width values only, so the hash doesn't depend on the platform
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(OFFSET)
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.0 = (self.0 ^ u64::from(value)).wrapping_mul(PRIME);
+    }
+
+    /// Little-endian, whatever the platform
+    pub fn write_u32(&mut self, value: u32) {
+        for &byte in value.to_le_bytes().iter() {
+            self.write_u8(byte);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
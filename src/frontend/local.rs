@@ -0,0 +1,277 @@
+//! Games played on this computer, and the commands around them.
+
+use rand::{Rng, XorShiftRng};
+use std::cmp;
+use std::io;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time;
+
+use audio;
+use backend::Backend;
+use broadcast;
+use capabilities;
+use cli::PlayArgs;
+use error::{Context, Result};
+use game::{self, Event, Mode};
+use keyboard::HoldTracker;
+use levels::Layout;
+use renderer::{self, Renderer, Surface};
+use replay::{Recorder, Replay};
+use scores::HighScores;
+use screen;
+use signals;
+use super::{default_settings, gamepad_threads, print, screen_size, suspend, tick_interval, tick_thread};
+use super::{HEIGHT, WIDTH};
+
+/// How long `bench` runs the game for
+const BENCH_TICKS: u32 = 10_000;
+/// Ticks between the random key presses in `bench`
+const BENCH_PRESS_TICKS: u32 = 8;
+
+/// Draws nothing, for timing the game alone
+struct FakeScreen {}
+
+impl Renderer for FakeScreen {
+    fn put_cell(&mut self, _x: u16, _y: u16, _c: char, _fg: renderer::Color, _bg: renderer::Color) {}
+}
+
+impl Surface for FakeScreen {
+    fn clear(&mut self) {}
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+pub fn play_local(args: PlayArgs) -> Result<()> {
+    let capabilities = capabilities::detect();
+    let mut settings = default_settings(&capabilities);
+    if let Some(theme) = args.theme {
+        settings.theme = theme;
+    }
+
+    let mut options = args.options;
+    let recorder = match args.record {
+        Some(ref path) => {
+            // Replaying needs the same boards
+            options.seed = Some(options.seed.unwrap_or_else(rand::random));
+            let recorder = Recorder::create(path, &options, args.speed, &settings)
+                .context(format!("Cannot record to {}", path.display()))?;
+            Some(recorder)
+        },
+        None => None,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let hold = Arc::new(Mutex::new(HoldTracker::default()));
+
+    Backend::from_env().spawn_input(tx.clone(), hold.clone());
+    gamepad_threads(&tx);
+    tick_thread(tx.clone(), hold.clone(), tick_interval(args.speed));
+    signals::spawn(tx).context("Cannot handle signals")?;
+
+    let game = game::init(settings, options);
+
+    let (width, height) = screen_size(&options);
+    let screen = screen::init(width, height, &capabilities).context("Cannot set up the terminal")?;
+
+    match args.broadcast {
+        Some(port) => {
+            let mut broadcaster = broadcast::Broadcaster::bind(port, screen, width, height)
+                .context(format!("Cannot broadcast on port {}", port))?;
+            run_local(&mut broadcaster, game, recorder, &hold, rx)
+        },
+        None => run_local(&mut { screen }, game, recorder, &hold, rx),
+    }
+}
+
+fn run_local<S: Surface>(screen: &mut S,
+                         mut game: game::Game,
+                         mut recorder: Option<Recorder>,
+                         hold: &Mutex<HoldTracker>,
+                         rx: mpsc::Receiver<Event>) -> Result<()> {
+    for event in rx {
+        let event = match event {
+            Event::Tick => {
+                if let Some(ref mut recorder) = recorder {
+                    recorder.tick().context("Cannot record the game")?;
+                }
+                if !game.handle_events() || !game.update() {
+                    break;
+                }
+                // The option may have just been changed
+                hold.lock().unwrap().set_enabled(game.settings().hold_to_move);
+
+                screen.clear();
+                game.render(screen);
+                screen.flush().context("Cannot draw the game")?;
+                continue;
+            },
+            Event::Quit => break,
+            Event::Suspend => {
+                suspend(screen)?;
+                Event::Pause
+            },
+            // Continued after being stopped some other way
+            Event::Resume => {
+                screen.resume().context("Cannot set up the terminal")?;
+                Event::Pause
+            },
+            Event::Terminate => {
+                game.save();
+                break;
+            },
+            e => e,
+        };
+
+        if let Some(ref mut recorder) = recorder {
+            recorder.push(event);
+        }
+        game.push_event(event);
+    }
+
+    match recorder {
+        Some(recorder) => recorder.finish().context("Cannot record the game"),
+        None => Ok(()),
+    }
+}
+
+pub fn replay(path: &Path) -> Result<()> {
+    let context = format!("Cannot replay {}", path.display());
+    let replay = Replay::load(path).context(context.as_str())?;
+    let theme = replay.find_theme().context(context.as_str())?;
+    replay.check_layouts().context(context.as_str())?;
+
+    let (tx, rx) = mpsc::channel();
+    Backend::from_env().spawn_input(tx.clone(), Arc::default());
+    tick_thread(tx.clone(), Arc::default(), tick_interval(replay.speed));
+    signals::spawn(tx).context("Cannot handle signals")?;
+
+    // Starting out like the recorded game did, whatever this terminal can show
+    let capabilities = capabilities::detect();
+    let mut settings = default_settings(&capabilities);
+    settings.theme = theme;
+    settings.color_blocks = replay.color_blocks;
+    settings.keep_scores = false;
+    let mut game = game::init(settings, replay.options);
+
+    let (width, height) = screen_size(&replay.options);
+    let mut screen = screen::init(width, height, &capabilities).context("Cannot set up the terminal")?;
+    run_replay(&mut screen, &mut game, &replay, rx)
+}
+
+fn run_replay(screen: &mut screen::Screen,
+              game: &mut game::Game,
+              replay: &Replay,
+              rx: mpsc::Receiver<Event>) -> Result<()> {
+    let mut tick = 0;
+    for event in rx {
+        match event {
+            Event::Tick => {
+                if tick == replay.length {
+                    return Ok(());
+                }
+                for &e in replay.events(tick) {
+                    game.push_event(e);
+                }
+                tick += 1;
+
+                if !game.handle_events() || !game.update() {
+                    return Ok(());
+                }
+
+                screen.clear();
+                game.render(screen);
+                screen.flush().context("Cannot draw the game")?;
+            },
+            // Only watching, so the keys just stop it
+            Event::Back | Event::Quit | Event::Terminate => return Ok(()),
+            Event::Suspend => suspend(screen)?,
+            Event::Resume => screen.resume().context("Cannot set up the terminal")?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+pub fn show_scores() -> Result<()> {
+    let scores = HighScores::load();
+    if scores.entries().is_empty() {
+        return print("No high scores yet");
+    }
+
+    let mut table = format!("{:>2}  {:>6}  {:>5}", "", "Score", "Level");
+    for (i, entry) in scores.entries().iter().enumerate() {
+        table.push_str(&format!("\n{:>2}. {:>6}  {:>5}", i + 1, entry.score, entry.level));
+    }
+    print(&table)
+}
+
+pub fn edit(args: PlayArgs) -> Result<()> {
+    let options = args.options;
+    // The size is only for a new layout, an existing one keeps its own
+    let layout = Layout::load(options.level)
+        .unwrap_or_else(|| Layout::blank(options.width, options.height));
+
+    let (tx, rx) = mpsc::channel();
+    let hold = Arc::new(Mutex::new(HoldTracker::default()));
+
+    Backend::from_env().spawn_input(tx.clone(), hold.clone());
+    tick_thread(tx.clone(), hold.clone(), tick_interval(1.0));
+    signals::spawn(tx).context("Cannot handle signals")?;
+
+    let capabilities = capabilities::detect();
+    let mut settings = default_settings(&capabilities);
+    if let Some(theme) = args.theme {
+        settings.theme = theme;
+    }
+
+    let (width, height) = (cmp::max(WIDTH, layout.width()), cmp::max(HEIGHT, layout.height() + 1));
+    let game = game::init_editor(options.level, layout, settings);
+    let screen = screen::init(width, height, &capabilities).context("Cannot set up the terminal")?;
+    run_local(&mut { screen }, game, None, &hold, rx)
+}
+
+/// Play with random keys as fast as possible, starting over when the game
+/// ends, and tell how long a tick takes
+pub fn bench(args: PlayArgs) -> Result<()> {
+    const KEYS: [Event; 5] = [Event::Up, Event::Down, Event::Left, Event::Right, Event::Select];
+
+    let options = args.options;
+    let seed = options.seed.unwrap_or(0);
+    let new_game = || {
+        let mut settings = game::Settings {
+            audio: audio::open("off"),
+            keep_scores: false,
+            ..game::Settings::default()
+        };
+        if let Some(ref theme) = args.theme {
+            settings.theme = theme.clone();
+        }
+        game::init_play(Mode::Single, options, seed, settings)
+    };
+
+    // The same keys on every run
+    let mut rng = XorShiftRng::new_unseeded();
+    let mut game = new_game();
+    let mut games = 1;
+    let mut screen = FakeScreen {};
+
+    let start = time::Instant::now();
+    for tick in 0..BENCH_TICKS {
+        if tick % BENCH_PRESS_TICKS == 0 {
+            game.push_event(*rng.choose(&KEYS).unwrap());
+        }
+        if !game.handle_events() || !game.update() {
+            game = new_game();
+            games += 1;
+        }
+
+        screen.clear();
+        game.render(&mut screen);
+        screen.flush().context("Cannot draw the game")?;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    print(&format!("{} ticks in {} games took {:.3}s: {:.0} ticks per second, {:.4} ms per tick",
+                   BENCH_TICKS, games, elapsed,
+                   BENCH_TICKS as f64 / elapsed, elapsed * 1000.0 / BENCH_TICKS as f64))
+}
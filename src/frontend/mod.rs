@@ -0,0 +1,100 @@
+//! Running the game in a terminal: input, ticks and drawing for each of
+//! the commands.
+
+mod local;
+mod network;
+
+use std::cmp;
+#[cfg(feature = "gamepad")]
+use std::env;
+use std::io::{self, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time;
+
+use capabilities::Capabilities;
+use cli;
+use error::{Context, Result};
+use game::{self, Event, PlayOptions};
+#[cfg(feature = "gamepad")]
+use gamepad;
+use keyboard::HoldTracker;
+use renderer::Surface;
+use signals;
+use theme::Theme;
+
+pub use self::local::{bench, edit, play_local, replay, show_scores};
+pub use self::network::{host, join, play_network, watch};
+
+/// Smallest screen, what the menus are drawn for
+const WIDTH: usize = 80;
+const HEIGHT: usize = 26;
+
+/// Time between ticks at normal speed
+const TICK_MILLIS: f64 = 60.0;
+
+/// Unlike `println!`, leaves a closed pipe to `main`
+pub fn print(text: &str) -> Result<()> {
+    writeln!(io::stdout(), "{}", text).context("Cannot write the output")
+}
+
+fn default_settings(capabilities: &Capabilities) -> game::Settings {
+    let mut settings = game::Settings::default();
+
+    // The other themes tell things apart by colour or need Unicode
+    if !capabilities.unicode || !capabilities.color {
+        settings.theme = Theme::ascii();
+    }
+    settings.color_blocks = capabilities.unicode && capabilities.color;
+    settings
+}
+
+/// Room for the board and its status line, and at least for the menus
+fn screen_size(options: &PlayOptions) -> (usize, usize) {
+    (cmp::max(WIDTH, options.width), cmp::max(HEIGHT, options.height + 1))
+}
+
+fn tick_interval(speed: f64) -> time::Duration {
+    // A replay file could say anything
+    let speed = if speed.is_nan() { 1.0 } else { speed.clamp(cli::MIN_SPEED, cli::MAX_SPEED) };
+    time::Duration::from_micros((TICK_MILLIS * 1000.0 / speed) as u64)
+}
+
+/// Give the terminal back to the shell and stop until continued
+fn suspend<S: Surface>(screen: &mut S) -> Result<()> {
+    screen.suspend();
+    signals::stop();
+    screen.resume().context("Cannot set up the terminal")
+}
+
+/// One device per player, from `RETRONIX_JOYSTICKS` (paths separated by `:`)
+#[cfg(feature = "gamepad")]
+fn gamepad_threads(tx: &mpsc::Sender<Event>) {
+    let paths = env::var("RETRONIX_JOYSTICKS")
+        .unwrap_or_else(|_| "/dev/input/js0:/dev/input/js1".to_string());
+
+    // Playing with the keyboard is fine when there's no gamepad
+    for (player, path) in paths.split(':').take(2).enumerate() {
+        let _ = gamepad::spawn(std::path::Path::new(path), player, tx.clone());
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+fn gamepad_threads(_tx: &mpsc::Sender<Event>) {}
+
+fn tick_thread(tx: mpsc::Sender<Event>, hold: Arc<Mutex<HoldTracker>>, interval: time::Duration) {
+    thread::spawn(move || {
+        loop {
+            // Keys let go since the last tick
+            let mut events = hold.lock().unwrap().expired();
+            events.push(Event::Tick);
+
+            // Stop once the game is over
+            if events.into_iter().any(|e| tx.send(e).is_err()) {
+                return;
+            }
+            thread::sleep(interval);
+        }
+    });
+
+}
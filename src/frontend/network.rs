@@ -0,0 +1,177 @@
+//! Network play and watching someone else's game.
+
+use std::io;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time;
+
+use backend::Backend;
+use broadcast;
+use capabilities;
+use error::{Context, Result};
+use game::{self, Event, Mode, PlayOptions};
+use keyboard::HoldTracker;
+use net;
+use renderer::{Renderer, Surface};
+use screen;
+use signals;
+use super::{default_settings, gamepad_threads, print, suspend, tick_interval, tick_thread};
+use super::{HEIGHT, WIDTH};
+
+pub fn host(port: u16, mode: Mode) -> Result<(net::Session, net::Setup)> {
+    print(&format!("Waiting for the other player on port {}...", port))?;
+    net::host(port, mode).context("Connection failed")
+}
+
+pub fn join(address: &str) -> Result<(net::Session, net::Setup)> {
+    net::join(address).context("Connection failed")
+}
+
+pub fn watch(address: &str) -> Result<()> {
+    let viewer = broadcast::Viewer::connect(address).context(format!("Cannot watch {}", address))?;
+
+    let (tx, rx) = mpsc::channel();
+    Backend::from_env().spawn_input(tx.clone(), Arc::default());
+    signals::spawn(tx).context("Cannot handle signals")?;
+
+    let (frames_tx, frames) = mpsc::channel();
+    let (width, height) = (viewer.width, viewer.height);
+    viewer.spawn(frames_tx);
+
+    let mut screen = screen::init(width, height, &capabilities::detect())
+        .context("Cannot set up the terminal")?;
+    run_viewer(&mut screen, &frames, &rx)
+}
+
+fn run_viewer(screen: &mut screen::Screen,
+              frames: &mpsc::Receiver<io::Result<broadcast::Frame>>,
+              input: &mpsc::Receiver<Event>) -> Result<()> {
+    loop {
+        match input.try_recv() {
+            // Spectators can only leave
+            Ok(Event::Back) | Ok(Event::Quit) | Ok(Event::Terminate) => return Ok(()),
+            Ok(Event::Suspend) => suspend(screen)?,
+            Ok(Event::Resume) => screen.resume().context("Cannot set up the terminal")?,
+            _ => {},
+        }
+
+        match frames.recv_timeout(time::Duration::from_millis(100)) {
+            Ok(frame) => {
+                // Cells not in the frame stay as they were
+                for (x, y, c, fg, bg) in frame.context("Stopped watching")? {
+                    screen.put_cell(x, y, c, fg, bg);
+                }
+                screen.flush().context("Cannot draw the game")?;
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+pub fn play_network((mut session, setup): (net::Session, net::Setup)) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let hold = Arc::new(Mutex::new(HoldTracker::default()));
+
+    Backend::from_env().spawn_input(tx.clone(), hold.clone());
+    gamepad_threads(&tx);
+    tick_thread(tx.clone(), hold, tick_interval(1.0));
+    signals::spawn(tx).context("Cannot handle signals")?;
+
+    let capabilities = capabilities::detect();
+    let mut screen = screen::init(WIDTH, HEIGHT, &capabilities).context("Cannot set up the terminal")?;
+    let mut game = game::init_play(setup.mode, PlayOptions::default(), setup.seed, default_settings(&capabilities));
+    let mut pending = vec![];
+
+    run_lockstep(&mut session, &mut game, &mut screen, &mut pending, rx)
+}
+
+fn run_lockstep(session: &mut net::Session,
+                game: &mut game::Game,
+                screen: &mut screen::Screen,
+                pending: &mut Vec<Event>,
+                rx: mpsc::Receiver<Event>) -> Result<()> {
+    for event in rx.iter() {
+        if event != Event::Tick {
+            if !local_event(event, session, game, screen, pending)? {
+                return Ok(());
+            }
+            continue;
+        }
+
+        session.send(pending, game.checksum()).context("Network game ended")?;
+        pending.clear();
+        let events = match wait_for_peer(session, game, screen, pending, &rx)? {
+            Some(events) => events,
+            None => return Ok(()),
+        };
+
+        // Both peers must apply the whole tick's input in the same order
+        for e in events {
+            game.push_event(e);
+        }
+
+        if !game.handle_events() || !game.update() {
+            return Ok(());
+        }
+
+        screen.clear();
+        game.render(screen);
+        screen.flush().context("Cannot draw the game")?;
+    }
+    Ok(())
+}
+
+/// The peer's input for the tick, or None if this side quit meanwhile.
+/// Ticks that come while waiting are dropped, so the game carries on at its
+/// pace afterwards rather than catching up in a burst.
+fn wait_for_peer(session: &mut net::Session,
+                 game: &mut game::Game,
+                 screen: &mut screen::Screen,
+                 pending: &mut Vec<Event>,
+                 rx: &mpsc::Receiver<Event>) -> Result<Option<Vec<Event>>> {
+    loop {
+        if let Some(events) = session.receive().context("Network game ended")? {
+            return Ok(Some(events));
+        }
+
+        loop {
+            match rx.try_recv() {
+                Ok(Event::Tick) => {},
+                Ok(event) => {
+                    if !local_event(event, session, game, screen, pending)? {
+                        return Ok(None);
+                    }
+                },
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Handle anything but a tick, returns false when it's time to leave
+fn local_event(event: Event,
+               session: &net::Session,
+               game: &game::Game,
+               screen: &mut screen::Screen,
+               pending: &mut Vec<Event>) -> Result<bool> {
+    match event {
+        Event::Quit => return Ok(false),
+        // Pausing goes through the other side too, to stay in step
+        Event::Suspend => {
+            suspend(screen)?;
+            pending.extend(session.local_event(Event::Pause));
+        },
+        Event::Resume => {
+            screen.resume().context("Cannot set up the terminal")?;
+            pending.extend(session.local_event(Event::Pause));
+        },
+        Event::Terminate => {
+            game.save();
+            return Ok(false);
+        },
+        // Clicks are left out, where they land depends on this screen
+        e => pending.extend(session.local_event(e)),
+    }
+    Ok(true)
+}
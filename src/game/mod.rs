@@ -1,28 +1,102 @@
+//! The game itself, as a stack of states.
+//!
+//! A [`Game`] holds [`State`]s, the top one getting the events and the
+//! ticks. Whatever a state returns as a [`Transition`] changes the stack:
+//! the menu pushes a level, game over pops back to the menu and so on. An
+//! empty stack ends the game.
+//!
+//! Nothing here draws to a terminal or waits for time to pass, whoever runs
+//! the game pushes events, ticks it and hands it a [`Renderer`].
+
+use std::cell::{Ref, RefCell};
 use std::collections::VecDeque;
+use std::rc::Rc;
+use levels::Layout;
 use renderer::Renderer;
 
+mod settings;
 mod states;
 
+pub use self::settings::{Settings, SharedSettings};
+pub use self::states::{Board, Field, Fill, LevelStats, Mode, PlayOptions, PlayState, Position, TARGET_FILL};
+
+/// Input and other things happening to the game
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Event {
+    /// Time to move on, every 60ms at normal speed
     Tick,
+    /// Player 1 up, also moves around the menus
     Up,
+    /// Player 1 down
     Down,
+    /// Player 1 left
     Left,
+    /// Player 1 right
     Right,
+    /// Player 2 up
+    Up2,
+    /// Player 2 down
+    Down2,
+    /// Player 2 left
+    Left2,
+    /// Player 2 right
+    Right2,
+    /// Pick the highlighted item, or carry on
     Select,
+    /// Leave the current screen
     Back,
+    /// The direction key being held was let go
+    Stop,
+    /// The same for player 2
+    Stop2,
+    /// Left mouse button pressed on the given cell
+    Click(u16, u16),
+    /// The input ended or Ctrl-C was pressed, time to leave
+    Quit,
+    /// Put the game on hold until the player is back
+    Pause,
+    /// Hand the terminal back to the shell and stop (Ctrl-Z)
+    Suspend,
+    /// Continued after being stopped
+    Resume,
+    /// Asked to exit (SIGTERM), keeping what can be kept
+    Terminate,
 }
 
+impl Event {
+    /// The same input as if it came from the given player's controls
+    pub fn for_player(self, index: usize) -> Event {
+        match (self, index) {
+            (Event::Up, 1) | (Event::Up2, 1) => Event::Up2,
+            (Event::Down, 1) | (Event::Down2, 1) => Event::Down2,
+            (Event::Left, 1) | (Event::Left2, 1) => Event::Left2,
+            (Event::Right, 1) | (Event::Right2, 1) => Event::Right2,
+            (Event::Stop, 1) | (Event::Stop2, 1) => Event::Stop2,
+            (Event::Up2, _) => Event::Up,
+            (Event::Down2, _) => Event::Down,
+            (Event::Left2, _) => Event::Left,
+            (Event::Right2, _) => Event::Right,
+            (Event::Stop2, _) => Event::Stop,
+            (event, _) => event,
+        }
+    }
+}
+
+/// What a state wants done with the stack
 pub enum Transition {
-    Push(Box<State>),
+    /// Put a state on top, to come back to this one later
+    Push(Box<dyn State>),
+    /// Remove this many states from the top
     Pop(usize),
-    Replace(Box<State>),
+    /// Swap this state for another one
+    Replace(Box<dyn State>),
+    /// Stay as things are
     None,
 }
 
 impl Transition {
-    /// Returns true if navigation succeeded
-    pub fn navigate(self, states: &mut Vec<Box<State>>) -> bool {
+    /// Returns false if it popped every state, meaning the game is over
+    pub fn navigate(self, states: &mut Vec<Box<dyn State>>) -> bool {
         match self {
             Transition::Push(next) => {
                 states.push(next);
@@ -46,22 +120,52 @@ impl Transition {
     }
 }
 
+/// One screen of the game, like the menu or a level being played
 pub trait State {
+    /// Called on every tick while on top of the stack
     fn update(&mut self) -> Transition;
-    fn render(&self, renderer: &mut Renderer);
+    /// Draw the state, over whatever the states below it drew
+    fn render(&self, renderer: &mut dyn Renderer);
+    /// Whether the state below shows through, for boxes drawn over a level
     fn render_parent(&self) -> bool;
+    /// Called with each event while on top of the stack
     fn handle_event(&mut self, event: Event) -> Transition;
+
+    /// Fingerprint of the simulation, used to detect desyncs in network play
+    fn checksum(&self) -> Option<u64> {
+        None
+    }
+
+    /// Keep what would be lost if the game ended right now
+    fn save(&self) {}
 }
 
+/// The stack of states, and the events waiting for the next tick
 pub struct Game {
     events: VecDeque<Event>,
-    states: Vec<Box<State>>,
+    states: Vec<Box<dyn State>>,
+    settings: SharedSettings,
 }
 
 // NOTE: Assumes there will be at least one state
 // (see unwrap in Game::current_state)
 impl Game {
-    pub fn render(&mut self, renderer: &mut Renderer) {
+    /// A game starting at the given state, sharing its settings
+    pub fn new(first: Box<dyn State>, settings: SharedSettings) -> Game {
+        Game {
+            events: VecDeque::new(),
+            states: vec![first],
+            settings,
+        }
+    }
+
+    /// The settings as the options screen left them
+    pub fn settings(&self) -> Ref<'_, Settings> {
+        self.settings.borrow()
+    }
+
+    /// Draw the top state, and the ones below that show through it
+    pub fn render(&mut self, renderer: &mut dyn Renderer) {
         let first = self.states.iter().rposition(|state| {
             !state.render_parent()
         }).unwrap();
@@ -71,33 +175,63 @@ impl Game {
         }
     }
 
+    /// Advance the top state by a tick, returns false once the game is over
     pub fn update(&mut self) -> bool {
         self.current_state().update().navigate(&mut self.states)
     }
 
+    /// Keep the event for `handle_events`
     pub fn push_event(&mut self, e: Event) {
         self.events.push_back(e);
     }
 
-    pub fn handle_event(&mut self) -> bool {
-        if self.events.is_empty() { return true; }
+    /// Handle everything that came in since the last tick, in order.
+    /// Returns false once the game is over.
+    pub fn handle_events(&mut self) -> bool {
+        while let Some(event) = self.events.pop_front() {
+            if !self.current_state().handle_event(event).navigate(&mut self.states) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Called before exiting in the middle of a game
+    pub fn save(&self) {
+        for state in self.states.iter() {
+            state.save();
+        }
+    }
 
-        let event = self.events.pop_front().unwrap();
-        self.current_state().handle_event(event).navigate(&mut self.states)
+    /// Fingerprint of the topmost state that has one, 0 if none do
+    pub fn checksum(&self) -> u64 {
+        self.states.iter().rev()
+            .filter_map(|state| state.checksum())
+            .next()
+            .unwrap_or(0)
     }
 
-    fn current_state(&mut self) -> &mut Box<State> {
+    fn current_state(&mut self) -> &mut Box<dyn State> {
         self.states.last_mut().unwrap()
     }
 }
 
-pub fn init() -> Game {
-    Game {
-        events: VecDeque::new(),
-        states: vec![
-            Box::new(states::WelcomeState {}),
-            // Box::new(states::PlayState::new(1, 0, 3)),
-            // Box::new(states::GameOverState {}),
-        ],
-    }
+/// Start playing without going through the menu
+pub fn init_play(mode: Mode, options: PlayOptions, seed: u64, settings: Settings) -> Game {
+    let settings = Rc::new(RefCell::new(settings));
+    Game::new(Box::new(states::PlayState::with_seed(mode, options, seed, settings.clone())), settings)
+}
+
+/// Edit the given level's layout, until ESC saves it
+pub fn init_editor(level: u32, layout: Layout, settings: Settings) -> Game {
+    let settings = Rc::new(RefCell::new(settings));
+    Game::new(Box::new(states::EditorState::new(level, layout, settings.clone())), settings)
+}
+
+/// Start at the title screen, games started from there use the options
+pub fn init(settings: Settings, options: PlayOptions) -> Game {
+    let settings = Rc::new(RefCell::new(settings));
+    Game::new(Box::new(states::WelcomeState::new(options, settings.clone())), settings)
+    // Game::new(Box::new(states::PlayState::new(states::Mode::Single, PlayOptions::default(), settings)))
+    // Game::new(Box::new(states::GameOverState::new("GAME OVER", (80, 24), settings)))
 }
@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use audio::{self, Audio};
+use theme::Theme;
+
+/// Preferences shared by all the states
+pub struct Settings {
+    /// How everything looks
+    pub theme: Theme,
+    /// Draw a taller board with two rows in each terminal row, where
+    /// `tall_board` says it can be
+    pub half_blocks: bool,
+    /// The terminal shows colours and the Unicode half blocks
+    pub color_blocks: bool,
+    /// Ignore turning back onto your own trail
+    pub no_reversal: bool,
+    /// Pressing the direction you're already going in stops you on land
+    pub tap_to_stop: bool,
+    /// Move only while a direction key is held down
+    pub hold_to_move: bool,
+    /// Where sound effects go
+    pub audio: Box<dyn Audio>,
+    /// Record scores in the table, not when watching a replay
+    pub keep_scores: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            theme: Theme::classic(),
+            half_blocks: false,
+            color_blocks: true,
+            no_reversal: true,
+            tap_to_stop: false,
+            hold_to_move: false,
+            audio: audio::open(audio::backends()[0]),
+            keep_scores: true,
+        }
+    }
+}
+
+impl Settings {
+    /// The tall board can be drawn with the terminal and theme in use
+    pub fn tall_board_possible(&self) -> bool {
+        self.color_blocks && self.theme.has_colors()
+    }
+
+    /// Draw the board tall, which falls back to the normal board when the
+    /// theme or the terminal can't show it
+    pub fn tall_board(&self) -> bool {
+        self.half_blocks && self.tall_board_possible()
+    }
+}
+
+/// Settings as the states hold them, so the options screen can change them
+pub type SharedSettings = Rc<RefCell<Settings>>;
@@ -0,0 +1,105 @@
+use game::{State, Event, Transition, SharedSettings};
+use levels::Layout;
+use renderer::Renderer;
+
+/// Drawing a level's land and sea by hand
+pub struct EditorState {
+    level: u32,
+    layout: Layout,
+    cursor: (usize, usize),
+    /// Shown on the status line until the next key
+    message: Option<String>,
+    settings: SharedSettings,
+}
+
+impl EditorState {
+    pub fn new(level: u32, layout: Layout, settings: SharedSettings) -> EditorState {
+        EditorState {
+            level,
+            cursor: (layout.width() / 2, layout.height() / 2),
+            layout,
+            message: None,
+            settings,
+        }
+    }
+
+    fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (x, y) = self.cursor;
+        let x = (x as isize + dx).max(0).min(self.layout.width() as isize - 1);
+        let y = (y as isize + dy).max(0).min(self.layout.height() as isize - 1);
+        self.cursor = (x as usize, y as usize);
+    }
+
+    fn toggle(&mut self) {
+        let (x, y) = self.cursor;
+        self.layout.toggle(x, y);
+    }
+
+    fn status(&self) -> String {
+        match self.message {
+            Some(ref message) => message.clone(),
+            None => format!("Level {}  Arrows: move  SPACE/click: land or sea  ESC: save and quit",
+                            self.level),
+        }
+    }
+}
+
+impl State for EditorState {
+    fn update(&mut self) -> Transition {
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let theme = &self.settings.borrow().theme;
+
+        for y in 0..self.layout.height() {
+            for x in 0..self.layout.width() {
+                let style = if (x, y) == self.cursor {
+                    theme.player[0]
+                } else if self.layout.is_land(x, y) {
+                    theme.land
+                } else {
+                    theme.sea
+                };
+                renderer.put_cell(x as u16, y as u16, style.glyph, style.fg, style.bg);
+            }
+        }
+
+        let row = self.layout.height() as u16;
+        for (x, c) in self.status().chars().take(self.layout.width()).enumerate() {
+            renderer.put_cell(x as u16, row, c, theme.hud.fg, theme.hud.bg);
+        }
+    }
+
+    fn render_parent(&self) -> bool {
+        false
+    }
+
+    fn save(&self) {
+        let _ = self.layout.save(self.level);
+    }
+
+    fn handle_event(&mut self, event: Event) -> Transition {
+        self.message = None;
+        match event {
+            Event::Up | Event::Up2 => self.move_cursor(0, -1),
+            Event::Down | Event::Down2 => self.move_cursor(0, 1),
+            Event::Left | Event::Left2 => self.move_cursor(-1, 0),
+            Event::Right | Event::Right2 => self.move_cursor(1, 0),
+            Event::Select => self.toggle(),
+            Event::Click(x, y) if (x as usize) < self.layout.width() && (y as usize) < self.layout.height() => {
+                self.cursor = (x as usize, y as usize);
+                self.toggle();
+            },
+            Event::Back => {
+                // Staying put gives another chance, or Ctrl-C to give up
+                match self.layout.save(self.level) {
+                    Ok(()) => return Transition::Pop(1),
+                    Err(e) => self.message = Some(format!("Cannot save: {}", e)),
+                }
+            },
+            _ => {},
+        }
+        Transition::None
+    }
+}
@@ -1,7 +1,22 @@
-use game::{State, Event, Transition};
-use renderer::{Renderer, Color};
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use theme::Panel;
 
 pub struct GameOverState {
+    title: &'static str,
+    panel: Panel,
+    settings: SharedSettings,
+}
+
+impl GameOverState {
+    /// Over a board of `size` columns and rows
+    pub fn new(title: &'static str, size: (u16, u16), settings: SharedSettings) -> GameOverState {
+        GameOverState {
+            title,
+            panel: Panel::centred(title.chars().count() as u16 + 4, 3, size),
+            settings,
+        }
+    }
 }
 
 impl State for GameOverState {
@@ -9,25 +24,21 @@ impl State for GameOverState {
         Transition::None
     }
 
-    fn render(&self, renderer: &mut Renderer) {
-        let msg = [
-            "╔═══════════╗",
-            "║ GAME OVER ║",
-            "╚═══════════╝",
-        ];
-
-        for (y, line) in msg.iter().enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                renderer.put_cell(x as u16 + 34, y as u16 + 11, c, Color::White);
-            }
-        }
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let theme = self.settings.borrow().theme.clone();
+        self.panel.draw(renderer, &theme.frame);
+        self.panel.centred_text(renderer, 1, self.title, theme.hud.fg, theme.frame.bg);
     }
 
     fn render_parent(&self) -> bool {
         true
     }
 
-    fn handle_event(&mut self, _event: Event) -> Transition {
-        Transition::Pop(2)
+    fn handle_event(&mut self, event: Event) -> Transition {
+        // Only on purpose, not a key still held from the game or a lost focus
+        match event {
+            Event::Select | Event::Back | Event::Click(..) => Transition::Pop(2),
+            _ => Transition::None,
+        }
     }
 }
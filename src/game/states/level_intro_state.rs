@@ -0,0 +1,91 @@
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use theme::Panel;
+use super::{PlayState, TARGET_FILL};
+
+/// Ticks before the level starts by itself
+const SHOW_TICKS: u32 = 50;
+
+/// Announces the level over its board before play starts
+pub struct LevelIntroState {
+    play: Option<PlayState>,
+    panel: Panel,
+    ticks_left: u32,
+    /// Paused, so only SPACE starts the level
+    waiting: bool,
+    settings: SharedSettings,
+}
+
+impl LevelIntroState {
+    pub fn new(play: PlayState, settings: SharedSettings) -> LevelIntroState {
+        LevelIntroState {
+            panel: Panel::centred(30, 9, play.size()),
+            play: Some(play),
+            ticks_left: SHOW_TICKS,
+            waiting: false,
+            settings,
+        }
+    }
+
+    fn start(&mut self) -> Transition {
+        match self.play.take() {
+            Some(play) => Transition::Replace(Box::new(play)),
+            None => Transition::None,
+        }
+    }
+}
+
+impl State for LevelIntroState {
+    fn update(&mut self) -> Transition {
+        if self.waiting {
+            return Transition::None;
+        }
+        if self.ticks_left == 0 {
+            return self.start();
+        }
+        self.ticks_left -= 1;
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let play = match self.play {
+            Some(ref play) => play,
+            None => return,
+        };
+        play.render(renderer);
+
+        let theme = self.settings.borrow().theme.clone();
+        let panel = &self.panel;
+        panel.draw(renderer, &theme.frame);
+
+        let (fg, bg) = (theme.hud.fg, theme.frame.bg);
+        panel.centred_text(renderer, 2, &format!("LEVEL {}", play.level()), fg, bg);
+        panel.centred_text(renderer, 4, &format!("Enemies: {}", play.enemy_count()), fg, bg);
+        panel.centred_text(renderer, 5, &format!("Fill {:.0}% of the sea", TARGET_FILL * 100.0), fg, bg);
+        panel.centred_text(renderer, 7, "SPACE to start", fg, bg);
+    }
+
+    fn render_parent(&self) -> bool {
+        false
+    }
+
+    fn save(&self) {
+        if let Some(ref play) = self.play {
+            play.save();
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> Transition {
+        match event {
+            Event::Select => self.start(),
+            // Not while paused, the click may just be bringing the terminal back into focus
+            Event::Click(..) if !self.waiting => self.start(),
+            Event::Back => Transition::Pop(1),
+            Event::Pause => {
+                self.waiting = true;
+                Transition::None
+            },
+            _ => Transition::None,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use theme::Panel;
+use super::{LevelIntroState, LevelStats, PlayState};
+
+/// Statistics of the level just cleared, shown until SPACE is pressed
+pub struct LevelSummaryState {
+    stats: LevelStats,
+    next: Option<PlayState>,
+    panel: Panel,
+    settings: SharedSettings,
+}
+
+impl LevelSummaryState {
+    pub fn new(stats: LevelStats, next: PlayState, settings: SharedSettings) -> LevelSummaryState {
+        LevelSummaryState {
+            stats,
+            panel: Panel::centred(30, 13, next.size()),
+            next: Some(next),
+            settings,
+        }
+    }
+}
+
+impl State for LevelSummaryState {
+    fn update(&mut self) -> Transition {
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let theme = self.settings.borrow().theme.clone();
+        let panel = &self.panel;
+        panel.draw(renderer, &theme.frame);
+
+        let (fg, bg) = (theme.hud.fg, theme.frame.bg);
+        let mut text = |x: u16, y: u16, s: &str| panel.text(renderer, x, y, s, fg, bg);
+
+        let stats = &self.stats;
+        text(7, 2, &format!("LEVEL {} CLEARED", stats.level));
+        let rows = [
+            ("Cells captured", stats.captured.to_string()),
+            ("Cuts made", stats.cuts.to_string()),
+            ("Time", stats.time()),
+            ("Bonuses", stats.bonuses.to_string()),
+            ("Deaths", stats.deaths.to_string()),
+        ];
+        for (i, &(label, ref value)) in rows.iter().enumerate() {
+            text(4, 4 + i as u16, &format!("{:<14}{:>8}", label, value));
+        }
+        text(7, 11, "SPACE to continue");
+    }
+
+    fn render_parent(&self) -> bool {
+        false
+    }
+
+    fn save(&self) {
+        if let Some(ref next) = self.next {
+            next.save();
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> Transition {
+        match event {
+            Event::Select | Event::Click(..) => match self.next.take() {
+                Some(next) => {
+                    Transition::Replace(Box::new(LevelIntroState::new(next, self.settings.clone())))
+                },
+                None => Transition::None,
+            },
+            Event::Back => Transition::Pop(1),
+            _ => Transition::None,
+        }
+    }
+}
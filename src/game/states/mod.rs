@@ -1,7 +1,17 @@
 mod play_state;
 mod welcome_state;
 mod game_over_state;
+mod options_state;
+mod level_intro_state;
+mod level_summary_state;
+mod pause_state;
+mod editor_state;
 
-pub use self::play_state::PlayState;
+pub use self::play_state::{PlayState, PlayOptions, Mode, LevelStats, Board, Field, Fill, Position, TARGET_FILL};
 pub use self::welcome_state::WelcomeState;
 pub use self::game_over_state::GameOverState;
+pub use self::options_state::OptionsState;
+pub use self::level_intro_state::LevelIntroState;
+pub use self::level_summary_state::LevelSummaryState;
+pub use self::pause_state::PauseState;
+pub use self::editor_state::EditorState;
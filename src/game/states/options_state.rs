@@ -0,0 +1,147 @@
+use audio;
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use theme::{Panel, Theme};
+
+const ORIGIN_X: u16 = 20;
+const ORIGIN_Y: u16 = 9;
+const WIDTH: u16 = 40;
+
+#[derive(Clone, Copy)]
+enum OptionItem {
+    Theme,
+    HalfBlocks,
+    Sound,
+    NoReversal,
+    TapToStop,
+    HoldToMove,
+}
+
+const ITEMS: [OptionItem; 6] = [
+    OptionItem::Theme,
+    OptionItem::HalfBlocks,
+    OptionItem::Sound,
+    OptionItem::NoReversal,
+    OptionItem::TapToStop,
+    OptionItem::HoldToMove,
+];
+
+pub struct OptionsState {
+    selected: usize,
+    themes: Vec<Theme>,
+    settings: SharedSettings,
+}
+
+impl OptionsState {
+    pub fn new(settings: SharedSettings) -> OptionsState {
+        OptionsState {
+            selected: 0,
+            themes: Theme::available(),
+            settings,
+        }
+    }
+
+    fn label(&self, item: OptionItem) -> String {
+        let settings = self.settings.borrow();
+        match item {
+            OptionItem::Theme => format!("Theme       < {} >", settings.theme.name),
+            OptionItem::HalfBlocks => {
+                let value = if settings.tall_board_possible() { on_off(settings.half_blocks) } else { "n/a" };
+                format!("Tall board  < {} >", value)
+            },
+            OptionItem::Sound => format!("Sound       < {} >", settings.audio.name()),
+            OptionItem::NoReversal => format!("No U-turns  < {} >", on_off(settings.no_reversal)),
+            OptionItem::TapToStop => format!("Tap to stop < {} >", on_off(settings.tap_to_stop)),
+            OptionItem::HoldToMove => format!("Hold to move < {} >", on_off(settings.hold_to_move)),
+        }
+    }
+
+    /// Step the selected option forwards or backwards through its values
+    fn change(&mut self, step: isize) {
+        let mut settings = self.settings.borrow_mut();
+        match ITEMS[self.selected] {
+            OptionItem::Theme => {
+                let current = self.themes.iter()
+                    .position(|t| t.name == settings.theme.name)
+                    .unwrap_or(0);
+                let count = self.themes.len() as isize;
+                let next = (current as isize + step + count) % count;
+                settings.theme = self.themes[next as usize].clone();
+            },
+            // Blank without colours, or all question marks without Unicode
+            OptionItem::HalfBlocks if !settings.tall_board_possible() => {},
+            OptionItem::HalfBlocks => settings.half_blocks = !settings.half_blocks,
+            OptionItem::NoReversal => settings.no_reversal = !settings.no_reversal,
+            OptionItem::TapToStop => settings.tap_to_stop = !settings.tap_to_stop,
+            OptionItem::HoldToMove => settings.hold_to_move = !settings.hold_to_move,
+            OptionItem::Sound => {
+                let backends = audio::backends();
+                let current = backends.iter()
+                    .position(|&name| name == settings.audio.name())
+                    .unwrap_or(0);
+                let count = backends.len() as isize;
+                let next = (current as isize + step + count) % count;
+                settings.audio = audio::open(backends[next as usize]);
+            },
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+impl State for OptionsState {
+    fn update(&mut self) -> Transition {
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let theme = self.settings.borrow().theme.clone();
+        let height = ITEMS.len() as u16 + 6;
+        let panel = Panel::new(ORIGIN_X, ORIGIN_Y, WIDTH, height);
+        panel.draw(renderer, &theme.border);
+
+        let mut text = |x: u16, y: u16, s: &str, style: ::theme::Style| {
+            panel.text(renderer, x, y, s, style.fg, style.bg);
+        };
+
+        text(16, 1, "OPTIONS", theme.hud);
+        for (i, &item) in ITEMS.iter().enumerate() {
+            let (marker, style) = if i == self.selected {
+                (theme.highlight.glyph, theme.highlight)
+            } else {
+                (' ', theme.hud)
+            };
+            text(2, 3 + i as u16, &format!("{} {}", marker, self.label(item)), style);
+        }
+        text(2, height - 2, "LEFT/RIGHT change, ESC back", theme.hud);
+    }
+
+    fn render_parent(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event) -> Transition {
+        match event {
+            Event::Up => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+            },
+            Event::Down => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+            },
+            Event::Left => self.change(-1),
+            Event::Right | Event::Select => self.change(1),
+            Event::Click(x, y) => {
+                let row = y.wrapping_sub(ORIGIN_Y + 3) as usize;
+                if (ORIGIN_X..ORIGIN_X + WIDTH).contains(&x) && row < ITEMS.len() {
+                    self.selected = row;
+                    self.change(1);
+                }
+            },
+            Event::Back => return Transition::Pop(1),
+            _ => {},
+        }
+        Transition::None
+    }
+}
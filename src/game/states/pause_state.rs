@@ -0,0 +1,50 @@
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use theme::Panel;
+
+/// Holds the game still over its board, until the player is back
+pub struct PauseState {
+    panel: Panel,
+    settings: SharedSettings,
+}
+
+impl PauseState {
+    /// Over a board of `size` columns and rows
+    pub fn new(size: (u16, u16), settings: SharedSettings) -> PauseState {
+        PauseState {
+            panel: Panel::centred(30, 8, size),
+            settings,
+        }
+    }
+}
+
+impl State for PauseState {
+    fn update(&mut self) -> Transition {
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let theme = self.settings.borrow().theme.clone();
+        let panel = &self.panel;
+        panel.draw(renderer, &theme.frame);
+
+        let (fg, bg) = (theme.hud.fg, theme.frame.bg);
+        panel.centred_text(renderer, 2, "PAUSED", fg, bg);
+        panel.centred_text(renderer, 4, "SPACE to continue", fg, bg);
+        panel.centred_text(renderer, 5, "ESC to leave the game", fg, bg);
+    }
+
+    fn render_parent(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event) -> Transition {
+        match event {
+            // Not on a click, that may just be bringing the terminal back into focus
+            Event::Select => Transition::Pop(1),
+            // Same as ESC during play
+            Event::Back => Transition::Pop(2),
+            _ => Transition::None,
+        }
+    }
+}
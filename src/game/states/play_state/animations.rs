@@ -0,0 +1,102 @@
+use renderer::Renderer;
+use theme::{Style, Theme};
+use super::Position;
+
+/// How many fields the flash of a capture spreads per tick
+const FLASH_SPEED: u32 = 2;
+/// How many fields wide the flash is
+const FLASH_WIDTH: u32 = 3;
+
+/// A short effect drawn on top of the board
+pub enum Animation {
+    /// The trail of a player who died crumbling away
+    Dissolve { player: usize, cells: Vec<Position> },
+    /// Freshly captured fields lighting up in waves from the closed trail
+    Flash { player: usize, cells: Vec<(Position, u32)> },
+    /// The board being swept clear at the end of a level
+    Wipe { width: usize, height: usize },
+}
+
+impl Animation {
+    fn duration(&self) -> u32 {
+        match *self {
+            Animation::Dissolve { .. } | Animation::Wipe { .. } => 20,
+            Animation::Flash { ref cells, .. } => {
+                let furthest = cells.iter().map(|&(_, distance)| distance).max().unwrap_or(0);
+                (furthest + FLASH_WIDTH) / FLASH_SPEED + 1
+            },
+        }
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer, theme: &Theme, tick: u32, duration: u32) {
+        let mut put = |position: &Position, style: Style| {
+            renderer.put_cell(position.x as u16, position.y as u16, style.glyph, style.fg, style.bg);
+        };
+
+        match *self {
+            Animation::Dissolve { player, ref cells } => {
+                // Scatter the order the fields go in, but the same way every time
+                let gone = cells.len() as u32 * tick / duration;
+                for position in cells.iter() {
+                    let order = scatter(position) % cells.len() as u32;
+                    if order < gone {
+                        put(position, theme.sea);
+                    } else if order < gone + 4 {
+                        put(position, theme.trail[player].with_glyph('░'));
+                    }
+                }
+            },
+            Animation::Flash { player, ref cells } => {
+                let front = tick * FLASH_SPEED;
+                for &(ref position, distance) in cells.iter() {
+                    if distance <= front && distance + FLASH_WIDTH > front {
+                        put(position, theme.player[player]);
+                    }
+                }
+            },
+            Animation::Wipe { width, height } => {
+                let swept = (width as u32 * (tick + 1) / duration) as i16;
+                for y in 0..height as i16 {
+                    for x in 0..swept {
+                        put(&Position { x, y }, theme.land);
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn scatter(position: &Position) -> u32 {
+    (position.x as u32).wrapping_mul(2_654_435_761) ^ (position.y as u32).wrapping_mul(40_503)
+}
+
+struct Playing {
+    animation: Animation,
+    tick: u32,
+    duration: u32,
+}
+
+#[derive(Default)]
+pub struct Animations {
+    playing: Vec<Playing>,
+}
+
+impl Animations {
+    pub fn play(&mut self, animation: Animation) {
+        let duration = animation.duration();
+        self.playing.push(Playing { animation, tick: 0, duration });
+    }
+
+    pub fn tick(&mut self) {
+        for playing in self.playing.iter_mut() {
+            playing.tick += 1;
+        }
+        self.playing.retain(|p| p.tick < p.duration);
+    }
+
+    pub fn render(&self, renderer: &mut dyn Renderer, theme: &Theme) {
+        for playing in self.playing.iter() {
+            playing.animation.render(renderer, theme, playing.tick, playing.duration);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::iter;
+
+struct Item {
+    text: String,
+    priority: u32,
+}
+
+/// The status line below the board. Items that don't fit the width are
+/// dropped, least important first, and the rest are spread out evenly.
+#[derive(Default)]
+pub struct Hud {
+    items: Vec<Item>,
+}
+
+impl Hud {
+    /// Items with a higher `priority` number are dropped first
+    pub fn add(&mut self, priority: u32, text: String) {
+        if !text.is_empty() {
+            self.items.push(Item { text, priority });
+        }
+    }
+
+    pub fn layout(&self, width: usize) -> String {
+        let len = |item: &&Item| item.text.chars().count();
+
+        let mut shown: Vec<&Item> = self.items.iter().collect();
+        while shown.len() > 1 && shown.iter().map(len).sum::<usize>() + shown.len() - 1 > width {
+            // Of equally important items the rightmost goes first
+            let least = (0..shown.len()).max_by_key(|&i| (shown[i].priority, i)).unwrap();
+            shown.remove(least);
+        }
+
+        let spare = width.saturating_sub(shown.iter().map(len).sum());
+        let gaps = shown.len().saturating_sub(1);
+
+        let mut line = String::new();
+        for (i, item) in shown.iter().enumerate() {
+            line.push_str(&item.text);
+            if i < gaps {
+                let gap = spare * (i + 1) / gaps - spare * i / gaps;
+                line.extend(iter::repeat(' ').take(gap));
+            }
+        }
+        line
+    }
+}
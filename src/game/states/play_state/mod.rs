@@ -1,18 +1,44 @@
+mod animations;
+mod hud;
 mod models;
+mod powerups;
 
-use rand::{thread_rng, Rng};
-use game::{Event, State, Transition};
-use renderer::{Renderer, Color};
-use self::models::{Board, Field};
+use rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+use std::collections::VecDeque;
+use audio::Sound;
+use fnv::Fnv;
+use game::{Event, State, Transition, SharedSettings};
+use levels::Layout;
+use renderer::{HalfBlocks, Renderer};
+use scores::{self, HighScores};
+use self::animations::{Animation, Animations};
+use self::hud::Hud;
+pub use self::models::{Board, Field, Fill};
+use self::powerups::{Effects, PowerUp, PowerUpKind, POWERUP_TTL};
 
-// TODO: Pass when constructing the state
 const BOARD_WIDTH: usize = 80;
 const BOARD_HEIGHT: usize = 25;
 
-#[derive(Clone, PartialEq)]
+/// Share of the sea to fill to finish a level
+pub const TARGET_FILL: f64 = 0.8;
+
+const MAX_POWERUPS: usize = 2;
+/// Chance (1 in N) that a power-up appears on any given tick
+const POWERUP_SPAWN_CHANCE: u32 = 150;
+/// How long a respawned player blinks for
+const RESPAWN_BLINK_TICKS: u32 = 30;
+/// Most turns a player can queue up ahead
+const TURN_BUFFER_SIZE: usize = 3;
+/// Queued turns that couldn't be made for this many ticks are dropped
+const TURN_BUFFER_TICKS: u32 = 8;
+
+/// A field on the board, counted from the top left corner
+#[derive(Clone, PartialEq, Hash, Debug)]
 pub struct Position {
-    x: i16,
-    y: i16,
+    /// Column
+    pub x: i16,
+    /// Row
+    pub y: i16,
 }
 
 impl Position {
@@ -24,7 +50,7 @@ impl Position {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Direction {
     dx: i16,
     dy: i16,
@@ -55,6 +81,10 @@ impl Direction {
         }
     }
 
+    fn is_reverse_of(&self, other: &Direction) -> bool {
+        *other != Direction::NONE && self.dx == -other.dx && self.dy == -other.dy
+    }
+
     fn flipped_x(&self) -> Direction {
         Direction {
             dx: -self.dx,
@@ -70,19 +100,56 @@ impl Direction {
     }
 }
 
-fn random_diagonal() -> Direction {
+fn random_diagonal<R: Rng>(rng: &mut R) -> Direction {
     static DIAGONALS: [Direction; 4] = [
         Direction::NORTHEAST,
         Direction::NORTHWEST,
         Direction::SOUTHEAST,
         Direction::SOUTHWEST,
     ];
-    thread_rng().choose(&DIAGONALS).unwrap().clone()
+    rng.choose(&DIAGONALS).unwrap().clone()
 }
 
 struct Player {
     position: Position,
     direction: Direction,
+    /// Where the last move went, still known after letting go of the keys
+    /// at sea, when turning straight back would hit the trail all the same
+    heading: Direction,
+    /// Last land position before entering the sea
+    trail_start: Position,
+    score: u32,
+    lives: u32,
+    /// Ticks left to blink for after respawning
+    blink: u32,
+    /// Turns pressed but not made yet, with the tick they were pressed on
+    turns: VecDeque<(Direction, u32)>,
+    /// Power-ups this player collected, which go when they die
+    effects: Effects,
+}
+
+impl Player {
+    fn new(position: Position, score: u32, lives: u32) -> Player {
+        Player {
+            trail_start: position.clone(),
+            position,
+            direction: Direction::NONE,
+            heading: Direction::NONE,
+            score,
+            lives,
+            blink: 0,
+            turns: VecDeque::new(),
+            effects: Effects::default(),
+        }
+    }
+
+    fn alive(&self) -> bool {
+        self.lives > 0
+    }
+
+    fn visible(&self) -> bool {
+        self.alive() && self.blink / 3 % 2 == 0
+    }
 }
 
 struct Enemy {
@@ -107,98 +174,307 @@ impl Enemy {
     }
 }
 
+/// Who plays
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    /// One player
+    Single,
+    /// Two players filling the board together
+    Coop,
+    /// Each player claims their own territory
+    Versus,
+}
+
+impl Mode {
+    fn player_count(self) -> usize {
+        match self {
+            Mode::Single => 1,
+            Mode::Coop | Mode::Versus => 2,
+        }
+    }
+}
+
+/// How a game starts, as given on the command line
+#[derive(Clone, Copy, Debug)]
+pub struct PlayOptions {
+    /// Level to start on, also the number of enemies on the sea
+    pub level: u32,
+    /// Lives each player starts with
+    pub lives: u32,
+    /// Every game plays on the same boards when given
+    pub seed: Option<u64>,
+    /// Columns of the board, including the border
+    pub width: usize,
+    /// Rows of the board in terminal cells, including the border but not
+    /// the status line. A tall board has twice as many, in the same space.
+    pub height: usize,
+}
+
+impl PlayOptions {
+    /// Smallest board, with room to play and for the level panels
+    pub const MIN_SIZE: (usize, usize) = (30, 14);
+    /// Largest board
+    pub const MAX_SIZE: (usize, usize) = (250, 100);
+
+    /// Says what's out of range, if anything
+    pub fn check(&self) -> Result<(), String> {
+        let (min_width, min_height) = Self::MIN_SIZE;
+        let (max_width, max_height) = Self::MAX_SIZE;
+        if !(1..=99).contains(&self.level) {
+            Err("The level must be between 1 and 99".to_string())
+        } else if !(1..=99).contains(&self.lives) {
+            Err("Lives must be between 1 and 99".to_string())
+        } else if !(min_width..=max_width).contains(&self.width) ||
+            !(min_height..=max_height).contains(&self.height) {
+            Err(format!("The board must be between {}x{} and {}x{}",
+                        min_width, min_height, max_width, max_height))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for PlayOptions {
+    fn default() -> PlayOptions {
+        PlayOptions {
+            level: 1,
+            lives: 3,
+            seed: None,
+            width: BOARD_WIDTH,
+            height: BOARD_HEIGHT,
+        }
+    }
+}
+
+/// What happened during a level, shown once it's cleared
+#[derive(Clone, Copy, Default)]
+pub struct LevelStats {
+    /// The level these are for
+    pub level: u32,
+    /// Fields turned into land
+    pub captured: u32,
+    /// Trails closed
+    pub cuts: u32,
+    /// Power-ups collected
+    pub bonuses: u32,
+    /// Lives lost
+    pub deaths: u32,
+    /// How long the level took
+    pub ticks: u32,
+}
+
+impl LevelStats {
+    /// How long the level took, as minutes and seconds
+    pub fn time(&self) -> String {
+        clock(self.ticks)
+    }
+}
+
+/// Game time as minutes and seconds, at 60ms per tick
+fn clock(ticks: u32) -> String {
+    let seconds = ticks * 60 / 1000;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+enum Collision {
+    Enemy,
+    Sand,
+}
+
 enum Delay {
-    Death(u32),
+    /// Ticks left and the index of the player who died
+    Death(u32, usize),
     NextLevel(u32),
     None,
 }
 
+/// A level being played, with everything on the board
 pub struct PlayState {
-    player: Player,
+    mode: Mode,
+    players: Vec<Player>,
     sea_enemies: Vec<Enemy>,
     land_enemies: Vec<Enemy>,
     board: Board,
     level: u32,
-    score: u32,
-    lives: u32,
     delay: Delay,
+    powerups: Vec<PowerUp>,
+    animations: Animations,
+    stats: LevelStats,
+    ticks: u32,
+    high_score: u32,
+    rng: XorShiftRng,
+    half_blocks: bool,
+    options: PlayOptions,
+    settings: SharedSettings,
 }
 
 impl PlayState {
-    pub fn new(level: u32, score: u32, lives: u32) -> PlayState {
-        let board = Board::new(BOARD_WIDTH, BOARD_HEIGHT);
+    /// A level to play, random unless the options have a seed
+    pub fn new(mode: Mode, options: PlayOptions, settings: SharedSettings) -> PlayState {
+        let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+        Self::with_seed(mode, options, seed, settings)
+    }
+
+    /// Same seed and same input always play out the same game
+    pub fn with_seed(mode: Mode, options: PlayOptions, seed: u64, settings: SharedSettings) -> PlayState {
+        let (level, lives) = (options.level, options.lives);
+        let (lo, hi) = (seed as u32, (seed >> 32) as u32);
+        // XorShift must not be seeded with all zeros
+        let mut rng = XorShiftRng::from_seed([lo, hi, lo ^ 0x9e37_79b9, hi ^ 0x7f4a_7c15]);
+
+        // Fixed for the whole game, the options can't change mid-level
+        let half_blocks = settings.borrow().tall_board();
+        // Two board rows in every terminal row but the status line
+        let height = if half_blocks { (options.height - 1) * 2 } else { options.height };
+        let mut board = Board::new(options.width, height);
+        if let Some(layout) = Layout::load(level) {
+            board.apply(&layout);
+        }
 
         let mut sea_enemies = vec![];
         for _ in 0..level {
             sea_enemies.push(Enemy {
-                position: board.random_position_of_type(Field::Sea),
-                direction: random_diagonal(),
+                position: board.random_position_of_type(Field::Sea, &mut rng),
+                direction: random_diagonal(&mut rng),
             });
         }
 
+        let players = (0..mode.player_count())
+            .map(|index| Player::new(Self::spawn_position(mode, index, &board), 0, lives))
+            .collect();
+
         PlayState {
-            player: Player {
-                position: Position {
-                    x: BOARD_WIDTH as i16 / 2,
-                    y: 0,
-                },
-                direction: Direction::NONE,
-            },
+            mode,
+            players,
             sea_enemies,
-            land_enemies: vec![
-                Enemy {
-                    position: Position {
-                        x: BOARD_WIDTH as i16 / 2,
-                        y: BOARD_HEIGHT as i16 - 2,
-                    },
-                    direction: random_diagonal(),
-                },
-            ],
+            land_enemies: vec![Self::new_land_enemy(&board, &mut rng)],
             board,
             level,
-            score,
-            lives,
             delay: Delay::None,
+            powerups: vec![],
+            animations: Animations::default(),
+            stats: LevelStats { level, ..LevelStats::default() },
+            ticks: 0,
+            high_score: HighScores::load().best(),
+            rng,
+            half_blocks,
+            options,
+            settings,
         }
     }
 
-    fn move_player(&mut self) {
-        let player = &mut self.player;
+    /// The level number
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Enemies on the sea and on land
+    pub fn enemy_count(&self) -> usize {
+        self.sea_enemies.len() + self.land_enemies.len()
+    }
+
+    /// The board as it is now, without the players and enemies on it
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Columns and terminal rows the board takes up, for centring things
+    /// over it
+    pub fn size(&self) -> (u16, u16) {
+        (self.board.width() as u16, self.board_rows() as u16)
+    }
+
+    fn next_level(&mut self) -> PlayState {
+        let seed = self.rng.gen();
+        let options = PlayOptions { level: self.level + 1, lives: 0, ..self.options };
+        let mut state = Self::with_seed(self.mode, options, seed, self.settings.clone());
+        for (next, player) in state.players.iter_mut().zip(self.players.iter()) {
+            next.score = player.score;
+            next.lives = player.lives;
+        }
+        state
+    }
+
+    fn spawn_position(mode: Mode, index: usize, board: &Board) -> Position {
+        let x = match mode {
+            Mode::Single => board.width() / 2,
+            Mode::Coop | Mode::Versus => board.width() * (index + 1) / 3,
+        };
+        Position { x: x as i16, y: 0 }
+    }
+
+    fn new_land_enemy<R: Rng>(board: &Board, rng: &mut R) -> Enemy {
+        Enemy {
+            position: Position {
+                x: board.width() as i16 / 2,
+                y: board.height() as i16 - 2,
+            },
+            direction: random_diagonal(rng),
+        }
+    }
+
+    /// Returns the players whose trails got enclosed by this move
+    fn move_player(&mut self, index: usize) -> Vec<usize> {
+        let player = &mut self.players[index];
+        // Standing still, which mustn't leave a trail under the player
+        if !player.alive() || player.direction == Direction::NONE {
+            return vec![];
+        }
+
         let pos = player.position.moved_to(&player.direction);
 
         if !self.board.within_bounds(&pos) {
             player.direction = Direction::NONE;
         } else {
+            player.heading = player.direction.clone();
+
+            if self.board[&player.position].is_land() && self.board[&pos] == Field::Sea {
+                player.trail_start = player.position.clone();
+            }
+
             if self.board[&player.position] == Field::Sea {
-                self.board[&player.position] = Field::Sand;
+                self.board[&player.position] = Field::Sand(index);
 
-                if self.board[&pos] == Field::Land {
+                if self.board[&pos].is_land() {
                     player.direction = Direction::NONE;
 
+                    let land = match self.mode {
+                        Mode::Versus => Field::Territory(index),
+                        _ => Field::Land,
+                    };
                     let enemy_positions: Vec<&Position> =
                         self.sea_enemies.iter().map(|e| &e.position).collect();
-                    self.score += self.board.fill(&enemy_positions);
+                    let fill = self.board.fill(index, &enemy_positions, land);
+                    player.score += fill.captured;
+                    player.position = pos;
+                    self.stats.captured += fill.captured;
+                    self.stats.cuts += 1;
+                    self.settings.borrow_mut().audio.play(Sound::TrailClosed);
+                    self.animations.play(Animation::Flash { player: index, cells: fill.cells });
+                    return fill.enclosed;
                 }
             }
 
             player.position = pos;
         }
+
+        vec![]
     }
 
     fn bounce_sea_enemies(&mut self) {
         for enemy in self.sea_enemies.iter_mut() {
             // Land in my horizontal direction?
-            if self.board[&enemy.position.moved_to(&enemy.direction.horizontal())] == Field::Land {
+            if self.board[&enemy.position.moved_to(&enemy.direction.horizontal())].is_land() {
                 enemy.direction = enemy.direction.flipped_x();
             }
 
             // Land in my vertical direction?
-            if self.board[&enemy.position.moved_to(&enemy.direction.vertical())] == Field::Land {
+            if self.board[&enemy.position.moved_to(&enemy.direction.vertical())].is_land() {
                 enemy.direction = enemy.direction.flipped_y();
             }
 
             // Land exactly in diagonal?
-            if self.board[&enemy.position.moved_to(&enemy.direction)] == Field::Land {
+            if self.board[&enemy.position.moved_to(&enemy.direction)].is_land() {
                 enemy.direction = enemy.direction.flipped_x().flipped_y();
             }
         }
@@ -214,19 +490,19 @@ impl PlayState {
         for enemy in self.land_enemies.iter_mut() {
             // Land in my horizontal direction?
             let pos = enemy.position.moved_to(&enemy.direction.horizontal());
-            if !self.board.within_bounds(&pos) || self.board[&pos] != Field::Land {
+            if !self.board.within_bounds(&pos) || !self.board[&pos].is_land() {
                 enemy.direction = enemy.direction.flipped_x();
             }
 
             // Land in my vertical direction?
             let pos = enemy.position.moved_to(&enemy.direction.vertical());
-            if !self.board.within_bounds(&pos) || self.board[&pos] != Field::Land {
+            if !self.board.within_bounds(&pos) || !self.board[&pos].is_land() {
                 enemy.direction = enemy.direction.flipped_y();
             }
 
             // Land exactly in diagonal?
             let pos = enemy.position.moved_to(&enemy.direction);
-            if !self.board.within_bounds(&pos) || self.board[&pos] != Field::Land {
+            if !self.board.within_bounds(&pos) || !self.board[&pos].is_land() {
                 enemy.direction = enemy.direction.flipped_x().flipped_y();
             }
         }
@@ -238,57 +514,415 @@ impl PlayState {
         }
     }
 
-    fn find_collision(&self) -> bool {
-        let position = &self.player.position.moved_to(&self.player.direction);
-        if self.board.within_bounds(&position) && self.board[&position] == Field::Sand {
-            return true;
+    /// Returns the index of the player who got hit, and how
+    fn find_collision(&self, enemies_move: bool) -> Option<(usize, Collision)> {
+        for (index, player) in self.players.iter().enumerate() {
+            if !player.alive() {
+                continue;
+            }
+
+            // Running into any trail, own or partner's
+            let position = &player.position.moved_to(&player.direction);
+            if self.board.within_bounds(position) {
+                if let Field::Sand(_) = self.board[position] {
+                    return Some((index, Collision::Sand));
+                }
+            }
+
+            let mut enemies = self.sea_enemies.iter().chain(self.land_enemies.iter());
+
+            // Frozen enemies only hurt when the player runs straight into them
+            if !enemies_move {
+                if enemies.any(|e| e.position == *position || e.position == player.position) {
+                    return Some((index, Collision::Enemy));
+                }
+                continue;
+            }
+
+            // if enemy WILL move (or move-vert/move-horiz) into player
+            if enemies.any(|e| e.hit_check(|p| *p == player.position)) {
+                return Some((index, Collision::Enemy));
+            }
+        }
+
+        if !enemies_move {
+            return None;
         }
 
         for enemy in &self.sea_enemies {
-            if enemy.hit_check(|position| {
-                *position == self.player.position || self.board[position] == Field::Sand
-            }) {
-                return true;
+            for position in enemy.hit_positions().iter() {
+                if let Field::Sand(owner) = self.board[position] {
+                    return Some((owner, Collision::Sand));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Undo the player's current trail and put them back where it started
+    fn rewind_trail(&mut self, index: usize) {
+        self.board.clean(index);
+        let player = &mut self.players[index];
+        player.position = player.trail_start.clone();
+        player.direction = Direction::NONE;
+        player.turns.clear();
+    }
+
+    fn enemies_move(&self) -> bool {
+        if self.effect_active(PowerUpKind::Freeze) {
+            return false;
+        }
+        !self.effect_active(PowerUpKind::SlowMotion) || self.ticks % 2 == 0
+    }
+
+    /// Whether any player has the effect, the enemies are everyone's
+    fn effect_active(&self, kind: PowerUpKind) -> bool {
+        self.players.iter().any(|p| p.effects.is_active(kind))
+    }
+
+    fn update_powerups(&mut self) {
+        for powerup in self.powerups.iter_mut() {
+            powerup.ttl -= 1;
+        }
+
+        // Expired or swallowed by the land
+        let board = &self.board;
+        self.powerups.retain(|p| p.ttl > 0 && board[&p.position] == Field::Sea);
+
+        let rng = &mut self.rng;
+        if self.powerups.len() < MAX_POWERUPS && rng.gen_weighted_bool(POWERUP_SPAWN_CHANCE) {
+            let position = self.board.random_position_of_type(Field::Sea, rng);
+            let occupied = self.sea_enemies.iter().any(|e| e.position == position) ||
+                self.powerups.iter().any(|p| p.position == position);
+
+            if !occupied {
+                self.powerups.push(PowerUp {
+                    kind: *rng.choose(&PowerUpKind::ALL).unwrap(),
+                    position,
+                    ttl: POWERUP_TTL,
+                });
             }
         }
+    }
 
-        // if land enemy WILL move (or move-vert/move-horiz) into player
-        for enemy in &self.land_enemies {
-            if enemy.hit_check(|p| *p == self.player.position) {
-                return true;
+    fn collect_powerups(&mut self) {
+        for player in self.players.iter_mut() {
+            if !player.alive() {
+                continue;
+            }
+
+            let collected: Vec<PowerUpKind> = self.powerups.iter()
+                .filter(|p| p.position == player.position)
+                .map(|p| p.kind)
+                .collect();
+            self.powerups.retain(|p| p.position != player.position);
+
+            for kind in collected {
+                self.stats.bonuses += 1;
+                match kind {
+                    PowerUpKind::ExtraLife => {
+                        player.lives += 1;
+                        self.settings.borrow_mut().audio.play(Sound::ExtraLife);
+                    },
+                    kind => player.effects.add(kind),
+                }
             }
         }
+    }
 
-        false
+    fn effects_status(&self, effects: &Effects) -> String {
+        let theme = &self.settings.borrow().theme;
+        let mut status = String::new();
+        for effect in effects.iter() {
+            status.push(theme.powerups[effect.kind.index()].glyph);
+            if let Some(ticks) = effect.ticks_left {
+                // 60ms per tick, rounded up to whole seconds
+                status.push_str(&format!("{}", (ticks * 60 + 999) / 1000));
+            }
+            status.push(' ');
+        }
+        status.trim_end().to_string()
     }
 
-    fn reset(&mut self) {
-        self.board.clean();
+    fn hud(&self) -> Hud {
+        let mut hud = Hud::default();
+        hud.add(0, format!("Level: {}", self.level));
 
-        self.player = Player {
-            position: Position { x: BOARD_WIDTH as i16 / 2, y: 0 },
-            direction: Direction { dx: 0, dy: 0 },
-        };
+        match self.mode {
+            Mode::Single => {
+                let player = &self.players[0];
+                hud.add(0, format!("Score: {}", player.score));
+                hud.add(0, format!("Xn: {}", player.lives));
+            },
+            Mode::Coop => {
+                for (i, player) in self.players.iter().enumerate() {
+                    hud.add(0, format!("P{}: {} Xn: {}", i + 1, player.score, player.lives));
+                }
+            },
+            Mode::Versus => {
+                for (i, player) in self.players.iter().enumerate() {
+                    hud.add(0, format!("P{}: {:.0}% Xn: {}",
+                                       i + 1, self.board.territory_ratio(i) * 100.0, player.lives));
+                }
+            },
+        }
 
-        self.land_enemies = vec![
-            Enemy {
-                position: Position {
-                    x: BOARD_WIDTH as i16 / 2,
-                    y: BOARD_HEIGHT as i16 - 2,
-                },
-                direction: random_diagonal(),
+        if self.mode == Mode::Single {
+            hud.add(1, self.effects_status(&self.players[0].effects));
+        } else {
+            for (i, player) in self.players.iter().enumerate() {
+                let status = self.effects_status(&player.effects);
+                if !status.is_empty() {
+                    hud.add(1, format!("P{} {}", i + 1, status));
+                }
+            }
+        }
+        hud.add(1, format!("Full: {:.0}/{:.0}%", self.board.fill_ratio * 100.0, TARGET_FILL * 100.0));
+        let remaining = (TARGET_FILL - self.board.fill_ratio).max(0.0);
+        hud.add(2, format!("To go: {:.0}%", remaining * 100.0));
+
+        hud.add(3, format!("Time: {}", clock(self.ticks)));
+        if self.mode != Mode::Versus {
+            hud.add(3, format!("Hi: {}", self.high_score.max(self.total_score())));
+        }
+        hud.add(4, format!("Enemies: {}", self.enemy_count()));
+        hud
+    }
+
+    /// Points scored so far, by all players together
+    pub fn total_score(&self) -> u32 {
+        self.players.iter().map(|p| p.score).sum()
+    }
+
+    /// Kill the player, returns false if that ends the game
+    fn kill(&mut self, index: usize) -> bool {
+        self.players[index].lives -= 1;
+        self.stats.deaths += 1;
+        self.delay = Delay::Death(20, index);
+        self.settings.borrow_mut().audio.play(Sound::Death);
+        self.animations.play(Animation::Dissolve {
+            player: index,
+            cells: self.board.positions_of(&Field::Sand(index)),
+        });
+
+        match self.mode {
+            Mode::Versus => self.players.iter().all(|p| p.alive()),
+            _ => self.players.iter().any(|p| p.alive()),
+        }
+    }
+
+    fn game_over(&self) -> Transition {
+        let title = match self.mode {
+            Mode::Versus => self.versus_result(),
+            _ => {
+                self.record_score();
+                "GAME OVER"
             },
-        ];
+        };
+        Transition::Push(Box::new(super::GameOverState::new(title, self.size(), self.settings.clone())))
     }
 
-    fn render_box(&self, renderer: &mut Renderer, text: &str) {
+    fn record_score(&self) {
+        if !self.settings.borrow().keep_scores {
+            return;
+        }
+        let mut scores = HighScores::load();
+        let entry = scores::Entry { score: self.total_score(), level: self.level };
+        // Not being able to save the score shouldn't get in the way of playing
+        if scores.record(entry) {
+            let _ = scores.save();
+        }
+    }
+
+    fn versus_result(&self) -> &'static str {
+        let (p1, p2) = (&self.players[0], &self.players[1]);
+        if p1.alive() != p2.alive() {
+            return if p1.alive() { "P1 WINS" } else { "P2 WINS" };
+        }
+
+        let (t1, t2) = (self.board.territory_ratio(0), self.board.territory_ratio(1));
+        if t1 > t2 {
+            "P1 WINS"
+        } else if t2 > t1 {
+            "P2 WINS"
+        } else {
+            "DRAW"
+        }
+    }
+
+    /// Respawn the player who died, without their effects. A partner still
+    /// playing carries on undisturbed, with the land enemy where it is.
+    fn reset(&mut self, index: usize) {
+        self.board.clean(index);
+        let partner_playing = self.players.iter().enumerate().any(|(i, p)| i != index && p.alive());
+
+        let player = &mut self.players[index];
+        *player = Player::new(Self::spawn_position(self.mode, index, &self.board),
+                              player.score, player.lives);
+        player.blink = RESPAWN_BLINK_TICKS;
+
+        if !partner_playing {
+            self.land_enemies = vec![Self::new_land_enemy(&self.board, &mut self.rng)];
+        }
+    }
+
+    /// Head player 1 towards a clicked cell, along whichever axis is further off
+    fn steer_to(&mut self, x: u16, y: u16) {
+        let player = &self.players[0].position;
+
+        // Terminal cells are twice as tall as wide, half blocks are square
+        let (dx, dy) = if self.half_blocks {
+            (x as i16 - player.x, y as i16 * 2 - player.y)
+        } else {
+            (x as i16 - player.x, (y as i16 - player.y) * 2)
+        };
+
+        let direction = if dx.abs() > dy.abs() {
+            if dx > 0 { Direction::EAST } else { Direction::WEST }
+        } else if dy != 0 {
+            if dy > 0 { Direction::SOUTH } else { Direction::NORTH }
+        } else {
+            return;
+        };
+        self.steer(0, direction);
+    }
+
+    /// Queue a turn, so quick presses all get made one field after another
+    fn steer(&mut self, index: usize, direction: Direction) {
+        let ticks = self.ticks;
+        let player = match self.players.get_mut(index) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let last = player.turns.back().map_or(&player.direction, |(d, _)| d);
+        let settings = self.settings.borrow();
+        // Held keys repeat, that mustn't count as tapping again
+        let tap_to_stop = settings.tap_to_stop && !settings.hold_to_move;
+        let direction = match (*last == direction, tap_to_stop) {
+            // Pressing the same key again means stop, if it's on land by then
+            (true, true) => Direction::NONE,
+            (true, false) => return,
+            (false, _) => direction,
+        };
+        if player.turns.len() == TURN_BUFFER_SIZE {
+            player.turns.pop_front();
+        }
+        player.turns.push_back((direction, ticks));
+    }
+
+    /// Letting go of the keys stops the player, wherever they are
+    fn stop(&mut self, index: usize) {
+        if !self.settings.borrow().hold_to_move {
+            return;
+        }
+        if let Some(player) = self.players.get_mut(index) {
+            player.turns.clear();
+            player.direction = Direction::NONE;
+        }
+    }
+
+    /// Make the oldest queued turn, or keep it for later if it would lead
+    /// off the board
+    fn next_turn(&mut self, index: usize) {
+        let ticks = self.ticks;
+        let no_reversal = self.settings.borrow().no_reversal;
+        let player = &mut self.players[index];
+        player.turns.retain(|&(_, pressed)| ticks - pressed <= TURN_BUFFER_TICKS);
+
+        let turn = match player.turns.front() {
+            Some((direction, _)) => direction.clone(),
+            None => return,
+        };
+
+        // Out at sea there's no stopping, and turning back means hitting the trail
+        let at_sea = !self.board[&player.position].is_land();
+        let stopping = turn == Direction::NONE;
+        let reversing = no_reversal && turn.is_reverse_of(&player.heading);
+        if at_sea && (stopping || reversing) {
+            player.turns.pop_front();
+            return;
+        }
+
+        if self.board.within_bounds(&player.position.moved_to(&turn)) {
+            player.direction = turn;
+            player.turns.pop_front();
+        }
+    }
+
+    /// Number of terminal rows the board takes up
+    fn board_rows(&self) -> usize {
+        if self.half_blocks {
+            (self.board.height() + 1) / 2
+        } else {
+            self.board.height()
+        }
+    }
+
+    fn render_board(&self, renderer: &mut dyn Renderer) {
+        let settings = self.settings.borrow();
+        let theme = &settings.theme;
+
+        for (y, row) in self.board.rows().enumerate() {
+            for (x, field) in row.iter().enumerate() {
+                let style = match *field {
+                    Field::Land => theme.land,
+                    Field::Sea => theme.sea,
+                    Field::Sand(index) => theme.trail[index],
+                    Field::Territory(index) => theme.territory[index],
+                };
+                renderer.put_cell(
+                    x as u16,
+                    y as u16,
+                    style.glyph,
+                    style.fg,
+                    style.bg,
+                );
+            }
+        }
+
+        self.animations.render(renderer, theme);
+
+        for p in self.powerups.iter() {
+            let style = theme.powerups[p.kind.index()];
+            renderer.put_cell(p.position.x as u16, p.position.y as u16, style.glyph, style.fg, style.bg);
+        }
+
+        for (player, style) in self.players.iter().zip(theme.player.iter()) {
+            if !player.visible() {
+                continue;
+            }
+            renderer.put_cell(
+                player.position.x as u16,
+                player.position.y as u16,
+                style.glyph,
+                style.fg,
+                style.bg,
+            );
+        }
+
+        for e in self.sea_enemies.iter() {
+            let style = theme.sea_enemy.frame(self.ticks);
+            renderer.put_cell(e.position.x as u16, e.position.y as u16, style.glyph, style.fg, style.bg);
+        }
+
+        for e in self.land_enemies.iter() {
+            let style = theme.land_enemy.frame(self.ticks);
+            renderer.put_cell(e.position.x as u16, e.position.y as u16, style.glyph, style.fg, style.bg);
+        }
+    }
+
+    fn render_box(&self, renderer: &mut dyn Renderer, text: &str) {
         let len = text.len() as u16;
 
-        let origin_x = (BOARD_WIDTH as u16 - len) / 2;
-        let origin_y = BOARD_HEIGHT as u16 / 2 - 1;
+        let origin_x = (self.board.width() as u16 - len) / 2;
+        let origin_y = self.board_rows() as u16 / 2 - 1;
 
+        let theme = &self.settings.borrow().theme;
         let mut draw = |x: u16, y: u16, c: char| {
-            renderer.put_cell(x + origin_x, y + origin_y, c, Color::White);
+            let style = theme.translate(c);
+            renderer.put_cell(x + origin_x, y + origin_y, style.glyph, style.fg, style.bg);
         };
 
         // ┌────┐
@@ -316,17 +950,21 @@ impl PlayState {
 
 impl State for PlayState {
     fn update(&mut self) -> Transition {
+        self.animations.tick();
+
         match self.delay {
-            Delay::Death(0) => {
+            Delay::Death(0, index) => {
                 self.delay = Delay::None;
-                self.reset();
+                self.reset(index);
                 return Transition::None;
             },
             Delay::NextLevel(0) => {
-                let next_level = Self::new(self.level + 1, self.score, self.lives);
-                return Transition::Replace(Box::new(next_level));
+                let stats = LevelStats { ticks: self.ticks, ..self.stats };
+                let next = self.next_level();
+                return Transition::Replace(Box::new(
+                    super::LevelSummaryState::new(stats, next, self.settings.clone())));
             },
-            Delay::Death(ref mut counter) | Delay::NextLevel(ref mut counter) => {
+            Delay::Death(ref mut counter, _) | Delay::NextLevel(ref mut counter) => {
                 *counter -= 1;
                 return Transition::None;
             }
@@ -334,74 +972,86 @@ impl State for PlayState {
         }
 
 
-        self.bounce_sea_enemies();
-        self.bounce_land_enemies();
-
-        if self.find_collision() {
-            self.lives -= 1;
-            if self.lives == 0 {
-                return Transition::Push(Box::new(super::GameOverState {}));
-            }
-
-            self.delay = Delay::Death(20);
-            return Transition::None;
+        self.ticks += 1;
+        for player in self.players.iter_mut() {
+            player.effects.tick();
+            player.blink = player.blink.saturating_sub(1);
         }
+        self.update_powerups();
 
-        self.move_player();
-        self.move_sea_enemies();
-        self.move_land_enemies();
+        // Turn first, so the collision check looks where the player is going
+        for index in 0..self.players.len() {
+            self.next_turn(index);
+        }
 
-        if self.board.fill_ratio > 0.8 {
-            self.delay = Delay::NextLevel(20);
+        let enemies_move = self.enemies_move();
+        if enemies_move {
+            self.bounce_sea_enemies();
+            self.bounce_land_enemies();
         }
 
-        Transition::None
-    }
+        match self.find_collision(enemies_move) {
+            // Only the player about to die is saved by their own shield
+            Some((index, Collision::Sand)) if self.players[index].effects.consume(PowerUpKind::Shield) => {
+                self.rewind_trail(index);
+                return Transition::None;
+            },
+            Some((index, _)) => {
+                if !self.kill(index) {
+                    return self.game_over();
+                }
+                return Transition::None;
+            },
+            None => {},
+        }
 
-    fn render(&self, renderer: &mut Renderer) {
-        for (y, row) in self.board.rows().enumerate() {
-            for (x, field) in row.iter().enumerate() {
-                let c = match field {
-                    &Field::Land => '█',
-                    &Field::Sea => '░',
-                    &Field::Sand => '▒',
-                    _ => '?',
-                };
-                renderer.put_cell(
-                    x as u16,
-                    y as u16,
-                    c,
-                    Color::Cyan,
-                );
+        for index in 0..self.players.len() {
+            for enclosed in self.move_player(index) {
+                // Only rivals die when trapped, a partner's trail is just absorbed
+                if self.mode == Mode::Versus && !self.kill(enclosed) {
+                    return self.game_over();
+                }
             }
         }
+        self.collect_powerups();
+        if enemies_move {
+            self.move_sea_enemies();
+            self.move_land_enemies();
+        }
 
-        renderer.put_cell(
-            self.player.position.x as u16,
-            self.player.position.y as u16,
-            '█',
-            Color::Magenta,
-        );
-
-        for e in self.sea_enemies.iter() {
-            renderer.put_cell(e.position.x as u16, e.position.y as u16, ' ', Color::Cyan);
+        if self.board.fill_ratio > TARGET_FILL {
+            if self.mode == Mode::Versus {
+                return self.game_over();
+            }
+            self.delay = Delay::NextLevel(20);
+            self.settings.borrow_mut().audio.play(Sound::LevelClear);
+            self.animations.play(Animation::Wipe {
+                width: self.board.width(),
+                height: self.board.height(),
+            });
         }
 
-        for e in self.land_enemies.iter() {
-            renderer.put_cell(e.position.x as u16, e.position.y as u16, ' ', Color::Cyan);
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        if self.half_blocks {
+            let mut pixels = HalfBlocks::new(self.board.width(), self.board.height());
+            self.render_board(&mut pixels);
+            pixels.draw(renderer);
+        } else {
+            self.render_board(renderer);
         }
 
-        let score = format!("Score: {:<10}                     Xn: {}                            Full: {:>2.0}%",
-                            self.score,
-                            self.lives,
-                            self.board.fill_ratio * 100.0);
-        for (x, c) in score.chars().take(BOARD_WIDTH).enumerate() {
-            renderer.put_cell(x as u16, self.board.rows().len() as u16, c, Color::White);
+        let theme = &self.settings.borrow().theme;
+        let status = self.hud().layout(self.board.width());
+        for (x, c) in status.chars().enumerate() {
+            renderer.put_cell(x as u16, self.board_rows() as u16, c, theme.hud.fg, theme.hud.bg);
         }
 
         match self.delay {
             Delay::NextLevel(_) => self.render_box(renderer, "YAY!"),
-            Delay::Death(_) => self.render_box(renderer, "OUCH!"),
+            Delay::Death(..) => self.render_box(renderer, "OUCH!"),
             _ => {},
         };
     }
@@ -410,26 +1060,150 @@ impl State for PlayState {
         false
     }
 
+    fn checksum(&self) -> Option<u64> {
+        // Both peers must come to the same number, whatever their platform
+        let mut hash = Fnv::new();
+        let position = |hash: &mut Fnv, position: &Position| {
+            hash.write_u32(position.x as u32);
+            hash.write_u32(position.y as u32);
+        };
+
+        hash.write_u32(self.ticks);
+        for row in self.board.rows() {
+            for field in row.iter() {
+                let (kind, owner) = match *field {
+                    Field::Land => (0, 0),
+                    Field::Sea => (1, 0),
+                    Field::Sand(owner) => (2, owner),
+                    Field::Territory(owner) => (3, owner),
+                };
+                hash.write_u8(kind);
+                hash.write_u8(owner as u8);
+            }
+        }
+        for player in self.players.iter() {
+            position(&mut hash, &player.position);
+            hash.write_u32(player.lives);
+            hash.write_u32(player.score);
+        }
+        for enemy in self.sea_enemies.iter().chain(self.land_enemies.iter()) {
+            position(&mut hash, &enemy.position);
+        }
+        Some(hash.finish())
+    }
+
+    fn save(&self) {
+        // Game over has already recorded it
+        if self.mode != Mode::Versus && self.players.iter().any(|p| p.alive()) {
+            self.record_score();
+        }
+    }
+
     fn handle_event(&mut self, event: Event) -> Transition {
         match event {
-            Event::Up => {
-                self.player.direction = Direction::NORTH;
-            },
-            Event::Down => {
-                self.player.direction = Direction::SOUTH;
-            },
-            Event::Left => {
-                self.player.direction = Direction::WEST;
-            },
-            Event::Right => {
-                self.player.direction = Direction::EAST;
-            },
+            Event::Up => self.steer(0, Direction::NORTH),
+            Event::Down => self.steer(0, Direction::SOUTH),
+            Event::Left => self.steer(0, Direction::WEST),
+            Event::Right => self.steer(0, Direction::EAST),
+            Event::Up2 => self.steer(1, Direction::NORTH),
+            Event::Down2 => self.steer(1, Direction::SOUTH),
+            Event::Left2 => self.steer(1, Direction::WEST),
+            Event::Right2 => self.steer(1, Direction::EAST),
+            Event::Stop => self.stop(0),
+            Event::Stop2 => self.stop(1),
+            Event::Click(x, y) => self.steer_to(x, y),
             Event::Back => {
                 return Transition::Pop(1);
             },
+            Event::Pause => {
+                return Transition::Push(Box::new(super::PauseState::new(self.size(), self.settings.clone())));
+            },
             _ => {},
         }
 
         Transition::None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use audio;
+    use game::{Event, Settings, State};
+    use super::{Board, Mode, PlayOptions, PlayState};
+    use super::powerups::PowerUpKind;
+
+    /// Players on an empty sea, without enemies to get in the way
+    fn empty_sea(mode: Mode) -> PlayState {
+        let settings = Settings { audio: audio::open("off"), keep_scores: false, ..Settings::default() };
+        let mut state = PlayState::with_seed(mode, PlayOptions::default(), 1,
+                                             Rc::new(RefCell::new(settings)));
+        state.board = Board::new(80, 25);
+        state.sea_enemies.clear();
+        state.land_enemies.clear();
+        state
+    }
+
+    fn steer(state: &mut PlayState, event: Event, ticks: u32) {
+        state.handle_event(event);
+        for _ in 0..ticks {
+            state.update();
+        }
+    }
+
+    #[test]
+    fn turning_into_own_trail_kills() {
+        let mut state = empty_sea(Mode::Single);
+        let lives = state.players[0].lives;
+
+        // Down into the sea, one step right, one up, then left onto the trail
+        steer(&mut state, Event::Down, 6);
+        steer(&mut state, Event::Right, 1);
+        steer(&mut state, Event::Up, 1);
+        assert_eq!(state.players[0].lives, lives);
+        steer(&mut state, Event::Left, 1);
+
+        assert_eq!(state.players[0].lives, lives - 1);
+    }
+
+    #[test]
+    fn no_turning_back_after_letting_go_at_sea() {
+        let mut state = empty_sea(Mode::Single);
+        state.settings.borrow_mut().hold_to_move = true;
+        let lives = state.players[0].lives;
+
+        steer(&mut state, Event::Down, 6);
+        steer(&mut state, Event::Stop, 1);
+        let position = state.players[0].position.clone();
+        steer(&mut state, Event::Up, 3);
+
+        assert_eq!(state.players[0].lives, lives);
+        assert_eq!(state.players[0].position, position);
+    }
+
+    #[test]
+    fn shields_only_protect_their_owner() {
+        let mut state = empty_sea(Mode::Versus);
+        state.players[0].effects.add(PowerUpKind::Shield);
+        let lives = state.players[1].lives;
+
+        // Player 2 runs into their own trail, player 1's shield is no help
+        steer(&mut state, Event::Down2, 6);
+        steer(&mut state, Event::Right2, 1);
+        steer(&mut state, Event::Up2, 1);
+        steer(&mut state, Event::Left2, 1);
+
+        assert_eq!(state.players[1].lives, lives - 1);
+        assert!(state.players[0].effects.is_active(PowerUpKind::Shield));
+    }
+
+    #[test]
+    fn checksum_stays_the_same() {
+        // Any change here breaks network play with older builds, and the
+        // value mustn't depend on the platform either
+        let mut state = empty_sea(Mode::Coop);
+        steer(&mut state, Event::Down, 5);
+        assert_eq!(state.checksum(), Some(0xc62d_a5b0_cfd9_3739));
+    }
+}
@@ -1,24 +1,55 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::collections::VecDeque;
 use std::ops::{Index, IndexMut};
+use levels::Layout;
 use super::Position;
 
-#[derive(Clone, PartialEq)]
+/// What a field of the board is
+#[derive(Clone, PartialEq, Hash, Debug)]
 pub enum Field {
+    /// Safe to walk on, where the land enemies roam
     Land,
+    /// Still to be filled, where the sea enemies bounce around
     Sea,
-    DeepSea,
-    Sand,
+    /// Trail left by the player with the given index
+    Sand(usize),
+    /// Land captured by the player with the given index
+    Territory(usize),
 }
 
+impl Field {
+    /// Land of anyone's
+    pub fn is_land(&self) -> bool {
+        match *self {
+            Field::Land | Field::Territory(_) => true,
+            Field::Sea | Field::Sand(_) => false,
+        }
+    }
+}
+
+/// Outcome of closing a trail with `Board::fill`
+pub struct Fill {
+    /// Number of fields turned into land
+    pub captured: u32,
+    /// Players whose trails ended up inside the captured area
+    pub enclosed: Vec<usize>,
+    /// Captured fields with their distance from the closed trail
+    pub cells: Vec<(Position, u32)>,
+}
+
+/// The playing field, a sea with a border of land two fields wide
 pub struct Board {
     fields: Vec<Vec<Field>>,
     width: usize,
     height: usize,
+    /// Sea at the start of the level, what the fill ratio is measured against
+    sea: usize,
+    /// Share of the sea turned into land so far
     pub fill_ratio: f64,
 }
 
 impl Board {
+    /// Needs at least 5 fields each way, to have some sea inside the border
     pub fn new(width: usize, height: usize) -> Board {
         let mut fields = vec![vec![Field::Sea; width]; height];
 
@@ -34,78 +65,173 @@ impl Board {
             fields,
             width,
             height,
+            sea: (width - 4) * (height - 4),
             fill_ratio: 0.0
         }
     }
 
-    pub fn rows(&self) -> ::std::slice::Iter<Vec<Field>> {
+    /// Put the layout's land on the board. Ignored if that would leave no
+    /// sea for the enemies.
+    pub fn apply(&mut self, layout: &Layout) {
+        let mut fields = self.fields.clone();
+        let mut sea = 0;
+        for (y, row) in fields.iter_mut().enumerate() {
+            for (x, field) in row.iter_mut().enumerate() {
+                if *field == Field::Sea && layout.is_land_scaled(x, y, self.width, self.height) {
+                    *field = Field::Land;
+                }
+                if *field == Field::Sea {
+                    sea += 1;
+                }
+            }
+        }
+
+        if sea > 0 {
+            self.fields = fields;
+            self.sea = sea;
+        }
+    }
+
+    /// Number of columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The fields row by row, from the top
+    pub fn rows(&self) -> ::std::slice::Iter<'_, Vec<Field>> {
         self.fields.iter()
     }
 
-    fn random_position(&self) -> Position {
-        let mut rng = thread_rng();
+    fn random_position<R: Rng>(&self, rng: &mut R) -> Position {
         let x = rng.gen_range(0, self.fields[0].len() as i16);
         let y = rng.gen_range(0, self.fields.len() as i16);
         Position { x, y }
     }
 
-    pub fn random_position_of_type(&self, field_type: Field) -> Position {
-        let mut pos = self.random_position();
+    /// Picks at random until it finds one, so there has to be such a field
+    pub fn random_position_of_type<R: Rng>(&self, field_type: Field, rng: &mut R) -> Position {
+        let mut pos = self.random_position(rng);
         while self.fields[pos.y as usize][pos.x as usize] != field_type {
-            pos = self.random_position();
+            pos = self.random_position(rng);
         }
         pos
     }
 
-    pub fn fill(&mut self, enemy_positions: &[&Position]) -> u32 {
-        fn flood_fill(fields: &mut Vec<Vec<Field>>, position: (i16, i16)) {
-            let mut q = VecDeque::new();
-            q.push_back(position);
-            while !q.is_empty() {
-                let (x, y) = q.pop_front().unwrap();
-                if fields[y as usize][x as usize] == Field::Sea {
-                    fields[y as usize][x as usize] = Field::DeepSea;
-                    q.push_back((x, y - 1));
-                    q.push_back((x, y + 1));
-                    q.push_back((x - 1, y));
-                    q.push_back((x + 1, y));
+    /// Close the player's trail, turning everything the enemies can't reach into `land`
+    pub fn fill(&mut self, player: usize, enemy_positions: &[&Position], land: Field) -> Fill {
+        // Enemies can reach through the sea and through other players' trails
+        let passable = |field: &Field| match *field {
+            Field::Sea => true,
+            Field::Sand(owner) => owner != player,
+            Field::Land | Field::Territory(_) => false,
+        };
+
+        let mut reachable = vec![vec![false; self.width]; self.height];
+        let mut q: VecDeque<(i16, i16)> = enemy_positions.iter()
+            .map(|pos| (pos.x, pos.y))
+            .collect();
+        while let Some((x, y)) = q.pop_front() {
+            let (ux, uy) = (x as usize, y as usize);
+            if reachable[uy][ux] || !passable(&self.fields[uy][ux]) {
+                continue;
+            }
+            reachable[uy][ux] = true;
+            q.push_back((x, y - 1));
+            q.push_back((x, y + 1));
+            q.push_back((x - 1, y));
+            q.push_back((x + 1, y));
+        }
+
+        let mut remaining_sea_fields = 0;
+        let mut fill = Fill {
+            captured: 0,
+            enclosed: vec![],
+            cells: vec![],
+        };
+        let mut captured = vec![vec![false; self.width]; self.height];
+        let mut trail = VecDeque::new();
+        for (y, (row, reachable_row)) in self.fields.iter_mut().zip(reachable.iter()).enumerate() {
+            for (x, (field, &reachable)) in row.iter_mut().zip(reachable_row.iter()).enumerate() {
+                if reachable {
+                    remaining_sea_fields += 1;
+                    continue;
+                }
+
+                match *field {
+                    Field::Land | Field::Territory(_) => continue,
+                    Field::Sand(owner) if owner == player => trail.push_back((x as i16, y as i16, 0)),
+                    Field::Sand(owner) if !fill.enclosed.contains(&owner) => {
+                        fill.enclosed.push(owner);
+                    },
+                    _ => {},
                 }
+                *field = land.clone();
+                captured[y][x] = true;
+                fill.captured += 1;
             }
         }
 
-        for pos in enemy_positions {
-            flood_fill(&mut self.fields, (pos.x, pos.y));
+        // Walk outwards from the trail through the captured area
+        while let Some((x, y, distance)) = trail.pop_front() {
+            let (ux, uy) = (x as usize, y as usize);
+            if !captured[uy][ux] {
+                continue;
+            }
+            captured[uy][ux] = false;
+            fill.cells.push((Position { x, y }, distance));
+            trail.push_back((x, y - 1, distance + 1));
+            trail.push_back((x, y + 1, distance + 1));
+            trail.push_back((x - 1, y, distance + 1));
+            trail.push_back((x + 1, y, distance + 1));
         }
 
-        let mut remaining_sea_fields = 0;
-        let mut new_land_fields = 0;
-        for row in self.fields.iter_mut() {
-            for field in row.iter_mut() {
-                if *field == Field::DeepSea {
-                    *field = Field::Sea;
-                    remaining_sea_fields += 1;
-                } else if *field == Field::Sea || *field == Field::Sand {
-                    *field = Field::Land;
-                    new_land_fields += 1;
+        self.fill_ratio = 1.0 - remaining_sea_fields as f64 / self.total_sea_fields() as f64;
+
+        fill
+    }
+
+    /// Positions of all the fields of the given type
+    pub fn positions_of(&self, field_type: &Field) -> Vec<Position> {
+        let mut positions = vec![];
+        for (y, row) in self.fields.iter().enumerate() {
+            for (x, field) in row.iter().enumerate() {
+                if field == field_type {
+                    positions.push(Position { x: x as i16, y: y as i16 });
                 }
             }
         }
+        positions
+    }
 
-        let total_sea_fields = (self.width - 4) * (self.height - 4);
-        self.fill_ratio = 1.0 - remaining_sea_fields as f64 / total_sea_fields as f64;
+    /// Share of the initial sea claimed by the given player
+    pub fn territory_ratio(&self, player: usize) -> f64 {
+        let claimed = self.fields.iter()
+            .flat_map(|row| row.iter())
+            .filter(|&field| *field == Field::Territory(player))
+            .count();
+        claimed as f64 / self.total_sea_fields() as f64
+    }
 
-        new_land_fields
+    fn total_sea_fields(&self) -> usize {
+        self.sea
     }
 
+    /// Whether the position is on the board at all
     pub fn within_bounds(&self, position: &Position) -> bool {
         position.x >= 0 && position.x < self.fields[0].len() as i16 &&
             position.y >= 0 && position.y < self.fields.len() as i16
     }
 
-    pub fn clean(&mut self) {
+    /// Turn the given player's trail back into sea
+    pub fn clean(&mut self, player: usize) {
         for row in self.fields.iter_mut() {
             for field in row.iter_mut() {
-                if *field == Field::Sand {
+                if *field == Field::Sand(player) {
                     *field = Field::Sea;
                 }
             }
@@ -113,7 +239,7 @@ impl Board {
     }
 }
 
-impl<'a> Index<&'a Position> for Board {
+impl Index<&Position> for Board {
     type Output = Field;
 
     fn index(&self, position: &Position) -> &Field {
@@ -121,7 +247,7 @@ impl<'a> Index<&'a Position> for Board {
     }
 }
 
-impl<'a> IndexMut<&'a Position>  for Board {
+impl IndexMut<&Position> for Board {
     fn index_mut(&mut self, position: &Position) -> &mut Field {
         &mut self.fields[position.y as usize][position.x as usize]
     }
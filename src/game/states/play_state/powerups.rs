@@ -0,0 +1,89 @@
+use super::Position;
+
+/// How long an uncollected power-up stays on the board
+pub const POWERUP_TTL: u32 = 300;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerUpKind {
+    Freeze,
+    SlowMotion,
+    ExtraLife,
+    Shield,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Freeze,
+        PowerUpKind::SlowMotion,
+        PowerUpKind::ExtraLife,
+        PowerUpKind::Shield,
+    ];
+
+    /// Position in `ALL`, also used to look up the power-up in a theme
+    pub fn index(self) -> usize {
+        PowerUpKind::ALL.iter().position(|&kind| kind == self).unwrap()
+    }
+
+    /// Number of ticks the effect lasts, or None if it's not timed
+    pub fn duration(self) -> Option<u32> {
+        match self {
+            PowerUpKind::Freeze => Some(100),
+            PowerUpKind::SlowMotion => Some(200),
+            PowerUpKind::ExtraLife | PowerUpKind::Shield => None,
+        }
+    }
+}
+
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub position: Position,
+    pub ttl: u32,
+}
+
+pub struct Effect {
+    pub kind: PowerUpKind,
+    pub ticks_left: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct Effects {
+    active: Vec<Effect>,
+}
+
+impl Effects {
+    pub fn add(&mut self, kind: PowerUpKind) {
+        let ticks_left = kind.duration();
+
+        // Collecting the same power-up again restarts its timer
+        if let Some(effect) = self.active.iter_mut().find(|e| e.kind == kind) {
+            effect.ticks_left = ticks_left;
+            return;
+        }
+
+        self.active.push(Effect { kind, ticks_left });
+    }
+
+    /// Returns true if the effect was active
+    pub fn consume(&mut self, kind: PowerUpKind) -> bool {
+        let len = self.active.len();
+        self.active.retain(|e| e.kind != kind);
+        self.active.len() != len
+    }
+
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.active.iter().any(|e| e.kind == kind)
+    }
+
+    pub fn tick(&mut self) {
+        for effect in self.active.iter_mut() {
+            if let Some(ref mut ticks) = effect.ticks_left {
+                *ticks = ticks.saturating_sub(1);
+            }
+        }
+        self.active.retain(|e| e.ticks_left != Some(0));
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, Effect> {
+        self.active.iter()
+    }
+}
@@ -1,8 +1,63 @@
-use game::{State, Event, Transition};
-use renderer::{Renderer, Color};
+use game::{State, Event, Transition, SharedSettings};
+use renderer::Renderer;
+use super::{Mode, PlayOptions};
+
+enum MenuItem {
+    Play(Mode),
+    Options,
+}
+
+const MENU_X: u16 = 31;
+const MENU_Y: u16 = 15;
+
+const MENU: [(&str, MenuItem); 4] = [
+    ("1 PLAYER", MenuItem::Play(Mode::Single)),
+    ("2 PLAYERS CO-OP", MenuItem::Play(Mode::Coop)),
+    ("2 PLAYERS VERSUS", MenuItem::Play(Mode::Versus)),
+    ("OPTIONS", MenuItem::Options),
+];
 
-#[allow(dead_code)]
 pub struct WelcomeState {
+    selected: usize,
+    /// For every game started from here
+    options: PlayOptions,
+    settings: SharedSettings,
+}
+
+impl WelcomeState {
+    pub fn new(options: PlayOptions, settings: SharedSettings) -> WelcomeState {
+        WelcomeState {
+            selected: 0,
+            options,
+            settings,
+        }
+    }
+
+    fn activate(&self) -> Transition {
+        let settings = self.settings.clone();
+        match MENU[self.selected].1 {
+            MenuItem::Play(mode) => {
+                let play = super::PlayState::new(mode, self.options, settings.clone());
+                Transition::Push(Box::new(super::LevelIntroState::new(play, settings)))
+            },
+            MenuItem::Options => {
+                Transition::Push(Box::new(super::OptionsState::new(settings)))
+            },
+        }
+    }
+
+    fn render_menu(&self, renderer: &mut dyn Renderer) {
+        let theme = &self.settings.borrow().theme;
+
+        for (i, &(label, _)) in MENU.iter().enumerate() {
+            let style = if i == self.selected { theme.highlight } else { theme.hud };
+            let marker = if i == self.selected { theme.highlight.glyph } else { ' ' };
+            let line = format!("{} {}", marker, label);
+            for (x, c) in line.chars().enumerate() {
+                renderer.put_cell(x as u16 + MENU_X, MENU_Y + i as u16, c, style.fg, style.bg);
+            }
+        }
+    }
 }
 
 impl State for WelcomeState {
@@ -10,7 +65,7 @@ impl State for WelcomeState {
         Transition::None
     }
 
-    fn render(&self, renderer: &mut Renderer) {
+    fn render(&self, renderer: &mut dyn Renderer) {
         let msg = [
             "┌──────────────────────────────────────────────────────────────────────────────┐",
             "│░░░░░░░░  ░░░░░░░░░ ░░░░░░░░ ░░░░░░░░   ░░░░░░░  ░░░░   ░░░ ░░░░░░░░ ░░░   ░░░│",
@@ -27,30 +82,28 @@ impl State for WelcomeState {
             "│                                                                              │",
             "│                                                                              │",
             "│                           ╔══════════════════════╗                           │",
-            "│                           ║ Press SPACE to start ║                           │",
+            "│                           ║                      ║                           │",
+            "│                           ║                      ║                           │",
+            "│                           ║                      ║                           │",
+            "│                           ║                      ║                           │",
             "│                           ║      ESC to quit     ║                           │",
             "│                           ╚══════════════════════╝                           │",
             "│                                                                              │",
-            "│                                                                              │",
-            "│                                                                              │",
-            "│                                                                              │",
             "│                            Made by Łukasz Adamczak                           │",
             "│                     Based on Xonix by Ilan Rav & Dani Katz                   │",
             "│                                                                              │",
             "└──────────────────────────────────────────────────────────────────────────────┘",
         ];
 
+        let theme = &self.settings.borrow().theme;
         for (y, line) in msg.iter().enumerate() {
             for (x, c) in line.chars().enumerate() {
-                let color = match c {
-                    '░' => Color::Magenta,
-                    '▒' => Color::Cyan,
-                    '█' => Color::White,
-                    _ => Color::White,
-                };
-                renderer.put_cell(x as u16, y as u16, c, color);
+                let style = theme.translate(c);
+                renderer.put_cell(x as u16, y as u16, style.glyph, style.fg, style.bg);
             }
         }
+
+        self.render_menu(renderer);
     }
 
     fn render_parent(&self) -> bool {
@@ -59,9 +112,23 @@ impl State for WelcomeState {
 
     fn handle_event(&mut self, event: Event) -> Transition {
         match event {
-            Event::Select => {
-                let state = super::PlayState::new(1, 0, 3);
-                Transition::Push(Box::new(state))
+            Event::Up => {
+                self.selected = (self.selected + MENU.len() - 1) % MENU.len();
+                Transition::None
+            },
+            Event::Down => {
+                self.selected = (self.selected + 1) % MENU.len();
+                Transition::None
+            },
+            Event::Select => self.activate(),
+            Event::Click(x, y) => {
+                // Anywhere on the item's row inside the menu box
+                let row = y.wrapping_sub(MENU_Y) as usize;
+                if (MENU_X - 3..MENU_X + 19).contains(&x) && row < MENU.len() {
+                    self.selected = row;
+                    return self.activate();
+                }
+                Transition::None
             },
             Event::Back => Transition::Pop(1),
             _ => Transition::None,
@@ -0,0 +1,174 @@
+//! Joysticks and gamepads through the Linux joystick API (`/dev/input/js*`).
+//!
+//! The device is read as a stream of 8 byte events in native byte order:
+//! a 32-bit timestamp, a 16-bit value, the event type and the number of the
+//! axis or button. Any file in that format can stand in for a device, which
+//! is handy for trying out the mapping without a gamepad.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use game::Event;
+
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+/// Set on the events describing the state at the time the device was opened
+const JS_EVENT_INIT: u8 = 0x80;
+
+/// How far a stick has to be pushed to count
+const AXIS_THRESHOLD: i16 = 16_384;
+
+/// Read the device on a separate thread, as the controls of the given player
+pub fn spawn(path: &Path, player: usize, tx: mpsc::Sender<Event>) -> io::Result<()> {
+    let device = File::open(path)?;
+    thread::spawn(move || read_events(device, player, &tx));
+    Ok(())
+}
+
+/// Forward events until the device is unplugged or the file ends
+pub fn read_events<R: Read>(mut device: R, player: usize, tx: &mpsc::Sender<Event>) {
+    // Which way each axis was last pushed, so holding a stick sends one event
+    let mut axes = [0i8; 256];
+    let mut buf = [0u8; 8];
+
+    while device.read_exact(&mut buf).is_ok() {
+        let value = i16::from_ne_bytes([buf[4], buf[5]]);
+        let (kind, number) = (buf[6], buf[7]);
+        // Nothing was pressed just now, only the sticks' positions matter
+        let init = kind & JS_EVENT_INIT != 0;
+
+        let event = match kind & !JS_EVENT_INIT {
+            JS_EVENT_BUTTON if value == 1 && !init => button(number),
+            JS_EVENT_AXIS => {
+                let pushed = if value <= -AXIS_THRESHOLD {
+                    -1
+                } else if value >= AXIS_THRESHOLD {
+                    1
+                } else {
+                    0
+                };
+                let previous = axes[number as usize];
+                axes[number as usize] = pushed;
+
+                if init || pushed == previous {
+                    None
+                } else if pushed == 0 {
+                    // Let go once neither the stick nor the d-pad points anywhere
+                    let centred = [0, 1, 6, 7].iter().all(|&i| axes[i] == 0);
+                    if centred && axis(number, previous).is_some() { Some(Event::Stop) } else { None }
+                } else {
+                    axis(number, pushed)
+                }
+            },
+            _ => None,
+        };
+
+        if let Some(event) = event {
+            if tx.send(event.for_player(player)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// A, Start → select; B, Back → back (Xbox layout)
+fn button(number: u8) -> Option<Event> {
+    match number {
+        0 | 7 => Some(Event::Select),
+        1 | 6 => Some(Event::Back),
+        _ => None,
+    }
+}
+
+/// The left stick and the d-pad, which most drivers report as axes 6 and 7
+fn axis(number: u8, pushed: i8) -> Option<Event> {
+    match (number, pushed) {
+        (0, -1) | (6, -1) => Some(Event::Left),
+        (0, 1) | (6, 1) => Some(Event::Right),
+        (1, -1) | (7, -1) => Some(Event::Up),
+        (1, 1) | (7, 1) => Some(Event::Down),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::mpsc;
+    use game::Event;
+    use super::{read_events, JS_EVENT_AXIS, JS_EVENT_BUTTON, JS_EVENT_INIT};
+
+    fn record(kind: u8, number: u8, value: i16) -> Vec<u8> {
+        let mut record = vec![0; 4];
+        record.extend_from_slice(&value.to_ne_bytes());
+        record.push(kind);
+        record.push(number);
+        record
+    }
+
+    fn events(records: &[Vec<u8>], player: usize) -> Vec<Event> {
+        let (tx, rx) = mpsc::channel();
+        read_events(Cursor::new(records.concat()), player, &tx);
+        drop(tx);
+        rx.iter().collect()
+    }
+
+    #[test]
+    fn stick_and_dpad_move_and_stop_when_centred() {
+        let records = [
+            record(JS_EVENT_AXIS, 0, -32_767),
+            record(JS_EVENT_AXIS, 0, -20_000),
+            record(JS_EVENT_AXIS, 0, 0),
+            record(JS_EVENT_AXIS, 7, 32_767),
+            record(JS_EVENT_AXIS, 6, 32_767),
+            record(JS_EVENT_AXIS, 7, 0),
+            record(JS_EVENT_AXIS, 6, 0),
+            record(JS_EVENT_AXIS, 1, -32_767),
+            record(JS_EVENT_AXIS, 1, 1_000),
+        ];
+        assert_eq!(events(&records, 0), vec![
+            Event::Left, Event::Stop,
+            Event::Down, Event::Right, Event::Stop,
+            Event::Up, Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn buttons_select_and_go_back() {
+        let records = [
+            record(JS_EVENT_BUTTON, 0, 1),
+            record(JS_EVENT_BUTTON, 0, 0),
+            record(JS_EVENT_BUTTON, 1, 1),
+            record(JS_EVENT_BUTTON, 7, 1),
+            record(JS_EVENT_BUTTON, 6, 1),
+            record(JS_EVENT_BUTTON, 3, 1),
+        ];
+        assert_eq!(events(&records, 0), vec![Event::Select, Event::Back, Event::Select, Event::Back]);
+    }
+
+    #[test]
+    fn second_player() {
+        let records = [
+            record(JS_EVENT_AXIS, 1, 32_767),
+            record(JS_EVENT_AXIS, 1, 0),
+            record(JS_EVENT_BUTTON, 0, 1),
+        ];
+        assert_eq!(events(&records, 1), vec![Event::Down2, Event::Stop2, Event::Select]);
+    }
+
+    #[test]
+    fn state_on_opening_sends_nothing() {
+        let records = [
+            record(JS_EVENT_BUTTON | JS_EVENT_INIT, 0, 1),
+            record(JS_EVENT_AXIS | JS_EVENT_INIT, 0, 32_767),
+            record(JS_EVENT_AXIS | JS_EVENT_INIT, 1, 0),
+            // Already pushed right, so only letting go counts
+            record(JS_EVENT_AXIS, 0, 32_767),
+            record(JS_EVENT_AXIS, 0, 0),
+        ];
+        assert_eq!(events(&records, 0), vec![Event::Stop]);
+    }
+}
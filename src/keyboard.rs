@@ -0,0 +1,98 @@
+//! Working out when a key is let go, for the hold-to-move controls.
+//!
+//! Terminals only send key presses, repeated while a key is held, so a key
+//! counts as let go once its repeats stop coming. Terminals speaking the
+//! kitty keyboard protocol report releases too, which the backends pass on.
+
+use std::time::{Duration, Instant};
+
+use game::Event;
+
+/// Terminals wait this long before repeating a held key, with some room
+/// for X11's default of 660ms
+const FIRST_REPEAT: Duration = Duration::from_millis(750);
+/// Longest gap between repeats of a held key
+const NEXT_REPEAT: Duration = Duration::from_millis(120);
+
+struct Held {
+    event: Event,
+    last: Instant,
+    repeated: bool,
+}
+
+/// Turns the direction keys each player holds into `Stop` events once let go.
+/// Keeps quiet until enabled, the other controls have no use for them.
+#[derive(Default)]
+pub struct HoldTracker {
+    held: [Option<Held>; 2],
+    enabled: bool,
+}
+
+impl HoldTracker {
+    /// Follows the "Hold to move" option, forgetting the keys when it's off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.held = [None, None];
+        }
+        self.enabled = enabled;
+    }
+
+    pub fn press(&mut self, event: Event) {
+        if !self.enabled {
+            return;
+        }
+        let player = match player_of(event) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let now = Instant::now();
+        match self.held[player] {
+            Some(ref mut held) if held.event == event => {
+                held.last = now;
+                held.repeated = true;
+            },
+            ref mut held => *held = Some(Held { event, last: now, repeated: false }),
+        }
+    }
+
+    /// Returns the `Stop` event if that was the key being held
+    pub fn release(&mut self, event: Event) -> Option<Event> {
+        let player = player_of(event)?;
+        match self.held[player] {
+            Some(ref held) if held.event == event => {
+                self.held[player] = None;
+                Some(Event::Stop.for_player(player))
+            },
+            _ => None,
+        }
+    }
+
+    /// `Stop` events for the keys whose repeats stopped coming
+    pub fn expired(&mut self) -> Vec<Event> {
+        let now = Instant::now();
+        let mut stops = vec![];
+        for (player, slot) in self.held.iter_mut().enumerate() {
+            let expired = match *slot {
+                Some(ref held) => {
+                    let wait = if held.repeated { NEXT_REPEAT } else { FIRST_REPEAT };
+                    now.duration_since(held.last) > wait
+                },
+                None => false,
+            };
+            if expired {
+                *slot = None;
+                stops.push(Event::Stop.for_player(player));
+            }
+        }
+        stops
+    }
+}
+
+fn player_of(event: Event) -> Option<usize> {
+    match event {
+        Event::Up | Event::Down | Event::Left | Event::Right => Some(0),
+        Event::Up2 | Event::Down2 | Event::Left2 | Event::Right2 => Some(1),
+        _ => None,
+    }
+}
@@ -0,0 +1,108 @@
+//! Hand-made level layouts, kept in `$XDG_DATA_HOME/retronix/levels` (or
+//! `~/.local/share/retronix/levels`) with one file per level number. Each
+//! line is a row of the board, `#` for land and `.` for sea.
+//!
+//! Layouts are stretched to whatever size the board has, so one drawn for
+//! the usual 80x25 board works on a tall or resized one too.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use dirs;
+use fnv::Fnv;
+
+/// Which fields of a level are land
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layout {
+    land: Vec<Vec<bool>>,
+}
+
+impl Layout {
+    /// All sea but the border
+    pub fn blank(width: usize, height: usize) -> Layout {
+        let mut land = vec![vec![false; width]; height];
+        for (y, row) in land.iter_mut().enumerate() {
+            for (x, field) in row.iter_mut().enumerate() {
+                *field = x < 2 || x >= width - 2 || y < 2 || y >= height - 2;
+            }
+        }
+        Layout { land }
+    }
+
+    /// None when the level has no layout, or it can't be read
+    pub fn load(level: u32) -> Option<Layout> {
+        let contents = fs::read_to_string(level_path(level)?).ok()?;
+        let land: Vec<Vec<bool>> = contents.lines()
+            .map(|line| line.chars().map(|c| c != '.').collect())
+            .collect();
+
+        // Ragged rows are cut down to the shortest one
+        let width = land.iter().map(|row| row.len()).min()?;
+        if width == 0 {
+            return None;
+        }
+        Some(Layout {
+            land: land.into_iter().map(|row| row[..width].to_vec()).collect(),
+        })
+    }
+
+    /// Keep the layout for the given level, replacing what it had
+    pub fn save(&self, level: u32) -> io::Result<()> {
+        let path = level_path(level)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for row in self.land.iter() {
+            contents.extend(row.iter().map(|&land| if land { '#' } else { '.' }));
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+
+    /// Number of columns
+    pub fn width(&self) -> usize {
+        self.land[0].len()
+    }
+
+    /// Number of rows
+    pub fn height(&self) -> usize {
+        self.land.len()
+    }
+
+    /// Whether the field at column `x` and row `y` is land
+    pub fn is_land(&self, x: usize, y: usize) -> bool {
+        self.land[y][x]
+    }
+
+    /// A hash of the layout that is the same on every computer, to tell
+    /// whether two of them match
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write_u32(self.width() as u32);
+        hash.write_u32(self.height() as u32);
+        for row in self.land.iter() {
+            for &land in row.iter() {
+                hash.write_u8(land as u8);
+            }
+        }
+        hash.finish()
+    }
+
+    /// Turn land into sea and sea into land
+    pub fn toggle(&mut self, x: usize, y: usize) {
+        self.land[y][x] = !self.land[y][x];
+    }
+
+    /// Whether a field of a board the given size is land, picking the
+    /// nearest cell of the layout
+    pub fn is_land_scaled(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        self.land[y * self.height() / height][x * self.width() / width]
+    }
+}
+
+fn level_path(level: u32) -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("levels").join(level.to_string()))
+}
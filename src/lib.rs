@@ -0,0 +1,55 @@
+//! A Xonix clone for the terminal.
+//!
+//! The game itself knows nothing about terminals. It is a stack of
+//! [`State`](game::State)s in a [`Game`](game::Game), fed
+//! [`Event`](game::Event)s and ticked by whoever runs it, drawing through the
+//! [`Renderer`](renderer::Renderer) trait:
+//!
+//! ```
+//! extern crate retronix;
+//!
+//! use retronix::audio;
+//! use retronix::game::{self, Event, Mode, PlayOptions, Settings};
+//! use retronix::renderer::{Color, Renderer};
+//!
+//! /// Counts what gets drawn
+//! struct Cells(usize);
+//!
+//! impl Renderer for Cells {
+//!     fn put_cell(&mut self, _x: u16, _y: u16, _c: char, _fg: Color, _bg: Color) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     // Quietly, and without touching the high scores
+//!     let settings = Settings { audio: audio::open("off"), keep_scores: false, ..Settings::default() };
+//!     let mut game = game::init_play(Mode::Single, PlayOptions::default(), 42, settings);
+//!
+//!     game.push_event(Event::Down);
+//!     for _ in 0..100 {
+//!         if !game.handle_events() || !game.update() {
+//!             break;
+//!         }
+//!     }
+//!
+//!     let mut cells = Cells(0);
+//!     game.render(&mut cells);
+//!     assert!(cells.0 >= 80 * 25);
+//! }
+//! ```
+//!
+//! The `retronix` binary is one such front-end, for the terminal.
+
+#![warn(missing_docs)]
+
+extern crate rand;
+
+pub mod audio;
+mod dirs;
+mod fnv;
+pub mod game;
+pub mod levels;
+pub mod renderer;
+pub mod scores;
+pub mod theme;
@@ -1,85 +1,73 @@
 extern crate termion;
 extern crate rand;
+extern crate libc;
+extern crate signal_hook;
+#[cfg(feature = "crossterm")]
+extern crate crossterm;
+extern crate retronix;
 
-mod game;
-mod renderer;
+mod backend;
+mod broadcast;
+mod capabilities;
+mod cli;
+mod error;
+mod frontend;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod keyboard;
+mod net;
+mod replay;
 mod screen;
+mod signals;
 
-use termion::event::Key;
-use termion::input::TermRead;
-use std::io;
-use std::sync::mpsc;
-use std::thread;
-use std::time;
+// The game itself comes from the library
+use retronix::{audio, game, levels, renderer, scores, theme};
 
-use game::Event;
+use std::env;
+use std::panic;
+use std::process;
 
-const WIDTH: usize = 80;
-const HEIGHT: usize = 26;
-
-#[allow(dead_code)]
-struct FakeScreen {}
-
-#[allow(dead_code)]
-impl FakeScreen {
-    fn clear(&mut self) {}
-    fn flush(&mut self) {}
-}
-
-impl renderer::Renderer for FakeScreen {
-    fn put_cell(&mut self, _x: u16, _y: u16, _c: char, _color: renderer::Color) {}
-}
+use cli::Command;
+use error::Result;
 
 fn main() {
-    let (tx, rx) = mpsc::channel();
-
-    input_thread(tx.clone());
-    tick_thread(tx.clone());
+    install_panic_hook();
 
-    let mut screen = screen::init(WIDTH, HEIGHT);
-    // let mut screen = FakeScreen {};
-    let mut game = game::init();
-
-    for event in rx {
-        match event {
-            Event::Tick => {
-                if !game.handle_event() || !game.update() {
-                    break;
-                }
-
-                screen.clear();
-                game.render(&mut screen);
-                screen.flush();
-            },
-            e => {
-                game.push_event(e);
-            },
+    // By now the terminal is back to normal, so the message stays visible
+    if let Err(e) = run() {
+        if e.is_broken_pipe() {
+            return;
         }
+        eprintln!("{}", e);
+        process::exit(e.exit_code());
     }
 }
 
-fn input_thread(tx: mpsc::Sender<Event>) {
-    thread::spawn(move || {
-        for c in io::stdin().keys() {
-            match c.unwrap() {
-                Key::Up        => tx.send(Event::Up).unwrap(),
-                Key::Down      => tx.send(Event::Down).unwrap(),
-                Key::Left      => tx.send(Event::Left).unwrap(),
-                Key::Right     => tx.send(Event::Right).unwrap(),
-                Key::Esc       => tx.send(Event::Back).unwrap(),
-                Key::Char(' ') => tx.send(Event::Select).unwrap(),
-                _ => {},
-            }
-        }
-    });
+/// Restore the terminal before the panic message is printed, whichever
+/// thread panicked, and stop rather than carry on without that thread
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        backend::restore();
+        default_hook(info);
+        process::exit(101);
+    }));
 }
 
-fn tick_thread(tx: mpsc::Sender<Event>) {
-    thread::spawn(move || {
-        loop {
-            tx.send(Event::Tick).unwrap();
-            thread::sleep(time::Duration::from_millis(60));
-        }
-    });
+fn run() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
 
+    match cli::parse(&args)? {
+        Command::Play(args) => frontend::play_local(args),
+        Command::Replay(path) => frontend::replay(&path),
+        Command::Scores => frontend::show_scores(),
+        Command::Edit(args) => frontend::edit(args),
+        Command::Bench(args) => frontend::bench(args),
+        Command::Host(port, mode) => frontend::play_network(frontend::host(port, mode)?),
+        Command::Join(address) => frontend::play_network(frontend::join(&address)?),
+        Command::Watch(address) => frontend::watch(&address),
+        Command::Help => frontend::print(cli::USAGE),
+        Command::Version => frontend::print(&format!("retronix {}", env!("CARGO_PKG_VERSION"))),
+    }
 }
@@ -0,0 +1,390 @@
+//! Lockstep network play over TCP.
+//!
+//! Both peers run the full simulation from a shared seed. Every tick each
+//! side sends the input it collected along with a checksum of its state,
+//! then waits for the other side to do the same. The host controls the
+//! first player, the client controls the second.
+//!
+//! The protocol is line based:
+//!
+//! ```text
+//! client: RETRONIX <version>
+//! host:   RETRONIX <version> <seed> <mode>    (or ERROR <reason>)
+//! both:   T <tick> <checksum> [<events>]      (once per tick)
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use rand::{thread_rng, Rng};
+
+use game::{Event, Mode};
+
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// Longest `Session::receive` waits before giving the caller a turn
+const WAIT: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    Host,
+    Client,
+}
+
+pub struct Session {
+    role: Role,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    tick: u64,
+    /// What went out for this tick, until the peer's reply comes in
+    sent: (Vec<Event>, u64),
+    /// Start of a line cut off by a timeout
+    partial: String,
+}
+
+/// Game settings agreed on during the handshake
+pub struct Setup {
+    pub seed: u64,
+    pub mode: Mode,
+}
+
+pub fn host(port: u16, mode: Mode) -> io::Result<(Session, Setup)> {
+    accept(TcpListener::bind(("0.0.0.0", port))?, mode)
+}
+
+/// Wait for the client on a socket that's already listening
+fn accept(listener: TcpListener, mode: Mode) -> io::Result<(Session, Setup)> {
+    let (stream, _) = listener.accept()?;
+    let mut session = Session::new(Role::Host, stream)?;
+
+    let hello = session.read_line()?;
+    let version = parse_hello(&hello)?;
+    if version != PROTOCOL_VERSION {
+        let reason = format!("version mismatch (host {}, client {})", PROTOCOL_VERSION, version);
+        session.write_line(&format!("ERROR {}", reason))?;
+        return Err(protocol_error(reason));
+    }
+
+    let setup = Setup {
+        seed: thread_rng().gen(),
+        mode,
+    };
+    session.write_line(&format!("RETRONIX {} {} {}",
+                                PROTOCOL_VERSION,
+                                setup.seed,
+                                mode_name(mode)))?;
+
+    session.start()?;
+    Ok((session, setup))
+}
+
+pub fn join(address: &str) -> io::Result<(Session, Setup)> {
+    let stream = TcpStream::connect(address)?;
+    let mut session = Session::new(Role::Client, stream)?;
+
+    session.write_line(&format!("RETRONIX {}", PROTOCOL_VERSION))?;
+
+    let reply = session.read_line()?;
+    if let Some(reason) = reply.strip_prefix("ERROR ") {
+        return Err(protocol_error(reason.to_string()));
+    }
+
+    let parts: Vec<&str> = reply.split_whitespace().collect();
+    if parts.len() != 4 || parts[0] != "RETRONIX" {
+        return Err(protocol_error(format!("unexpected handshake: {}", reply)));
+    }
+
+    let version: u32 = parse(parts[1])?;
+    if version != PROTOCOL_VERSION {
+        return Err(protocol_error(
+            format!("version mismatch (host {}, client {})", version, PROTOCOL_VERSION)));
+    }
+
+    let setup = Setup {
+        seed: parse(parts[2])?,
+        mode: parse_mode(parts[3])?,
+    };
+
+    session.start()?;
+    Ok((session, setup))
+}
+
+impl Session {
+    fn new(role: Role, stream: TcpStream) -> io::Result<Session> {
+        stream.set_nodelay(true)?;
+        Ok(Session {
+            role,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            tick: 0,
+            sent: (vec![], 0),
+            partial: String::new(),
+        })
+    }
+
+    /// Translate a key press into an event for the player this peer
+    /// controls, or None for one that can't go to the peer, like a click
+    pub fn local_event(&self, event: Event) -> Option<Event> {
+        encode(event)?;
+        match self.role {
+            Role::Host => Some(event.for_player(0)),
+            Role::Client => Some(event.for_player(1)),
+        }
+    }
+
+    /// Send this tick's input, `receive` brings the peer's. Events that
+    /// can't be sent are left out on this side too, or the games would
+    /// drift apart.
+    pub fn send(&mut self, events: &[Event], checksum: u64) -> io::Result<()> {
+        let events: Vec<Event> = events.iter().cloned().filter(|&e| encode(e).is_some()).collect();
+        let codes: String = events.iter().filter_map(|&e| encode(e)).collect();
+        let line = format!("T {} {:x} {}", self.tick, checksum, codes);
+        self.write_line(line.trim_end())?;
+        self.sent = (events, checksum);
+        Ok(())
+    }
+
+    /// The peer's input for the tick last sent, or None if it didn't come
+    /// in time and it's worth asking again.
+    ///
+    /// Returns the events of both players in the same order on both sides.
+    pub fn receive(&mut self) -> io::Result<Option<Vec<Event>>> {
+        let reply = match self.read_line() {
+            Ok(reply) => reply,
+            Err(ref e) if is_timeout(e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let parts: Vec<&str> = reply.split_whitespace().collect();
+        if parts.len() < 3 || parts[0] != "T" {
+            return Err(protocol_error(format!("unexpected message: {}", reply)));
+        }
+
+        let tick: u64 = parse(parts[1])?;
+        if tick != self.tick {
+            return Err(protocol_error(format!("expected tick {}, got {}", self.tick, tick)));
+        }
+
+        let (ref events, checksum) = self.sent;
+        let remote_checksum = u64::from_str_radix(parts[2], 16)
+            .map_err(|_| protocol_error(format!("invalid checksum: {}", parts[2])))?;
+        if remote_checksum != checksum {
+            return Err(protocol_error(format!("desync detected at tick {}", self.tick)));
+        }
+
+        let mut remote = vec![];
+        for c in parts.get(3).unwrap_or(&"").chars() {
+            remote.push(decode(c).ok_or_else(|| protocol_error(format!("invalid event: {}", c)))?);
+        }
+
+        self.tick += 1;
+
+        // Host input always goes first
+        let mut all = vec![];
+        match self.role {
+            Role::Host => {
+                all.extend_from_slice(events);
+                all.extend(remote);
+            },
+            Role::Client => {
+                all.extend(remote);
+                all.extend_from_slice(events);
+            },
+        }
+        Ok(Some(all))
+    }
+
+    /// Done with the handshake, from now on reads give up after a while so
+    /// a stalled peer doesn't freeze this side
+    fn start(&mut self) -> io::Result<()> {
+        self.writer.set_read_timeout(Some(WAIT))
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        // What came before a timeout stays in `partial` for the next try
+        if self.reader.read_line(&mut self.partial)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by peer"));
+        }
+        let line = self.partial.trim_end().to_string();
+        self.partial.clear();
+        Ok(line)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+}
+
+pub fn encode(event: Event) -> Option<char> {
+    match event {
+        Event::Up => Some('U'),
+        Event::Down => Some('D'),
+        Event::Left => Some('L'),
+        Event::Right => Some('R'),
+        Event::Up2 => Some('u'),
+        Event::Down2 => Some('d'),
+        Event::Left2 => Some('l'),
+        Event::Right2 => Some('r'),
+        Event::Select => Some('S'),
+        Event::Back => Some('B'),
+        Event::Stop => Some('H'),
+        Event::Stop2 => Some('h'),
+        // Both games have to pause together to stay in step
+        Event::Pause => Some('P'),
+        // Where a click lands depends on the local screen, so only keys are sent
+        Event::Tick | Event::Click(..) => None,
+        // Handled by each side's main loop
+        Event::Quit | Event::Suspend | Event::Resume | Event::Terminate => None,
+    }
+}
+
+pub fn decode(c: char) -> Option<Event> {
+    match c {
+        'U' => Some(Event::Up),
+        'D' => Some(Event::Down),
+        'L' => Some(Event::Left),
+        'R' => Some(Event::Right),
+        'u' => Some(Event::Up2),
+        'd' => Some(Event::Down2),
+        'l' => Some(Event::Left2),
+        'r' => Some(Event::Right2),
+        'S' => Some(Event::Select),
+        'B' => Some(Event::Back),
+        'H' => Some(Event::Stop),
+        'h' => Some(Event::Stop2),
+        'P' => Some(Event::Pause),
+        _ => None,
+    }
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Single => "single",
+        Mode::Coop => "coop",
+        Mode::Versus => "versus",
+    }
+}
+
+fn parse_mode(name: &str) -> io::Result<Mode> {
+    match name {
+        "coop" => Ok(Mode::Coop),
+        "versus" => Ok(Mode::Versus),
+        _ => Err(protocol_error(format!("unsupported mode: {}", name))),
+    }
+}
+
+fn parse_hello(line: &str) -> io::Result<u32> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() != 2 || parts[0] != "RETRONIX" {
+        return Err(protocol_error(format!("unexpected handshake: {}", line)));
+    }
+    parse(parts[1])
+}
+
+fn parse<T: ::std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse().map_err(|_| protocol_error(format!("invalid number: {}", s)))
+}
+
+/// Unix and Windows report a read timeout differently
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+fn protocol_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use game::{Event, Mode};
+    use super::{accept, decode, encode, join, Session, Setup, PROTOCOL_VERSION};
+
+    /// A host and a client connected over localhost
+    fn connect(mode: Mode) -> ((Session, Setup), (Session, Setup)) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let host = thread::spawn(move || accept(listener, mode).unwrap());
+        let client = join(&address).unwrap();
+        (host.join().unwrap(), client)
+    }
+
+    #[test]
+    fn handshake_agrees_on_the_game() {
+        let ((_, host), (_, client)) = connect(Mode::Versus);
+        assert_eq!(host.seed, client.seed);
+        assert_eq!((host.mode, client.mode), (Mode::Versus, Mode::Versus));
+    }
+
+    #[test]
+    fn other_versions_are_turned_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || accept(listener, Mode::Coop).err().unwrap());
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        writeln!(stream, "RETRONIX {}", PROTOCOL_VERSION + 1).unwrap();
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).unwrap();
+
+        assert!(reply.starts_with("ERROR version mismatch"), "{}", reply);
+        assert!(host.join().unwrap().to_string().contains("version mismatch"));
+    }
+
+    #[test]
+    fn events_survive_the_trip() {
+        let events = [
+            Event::Up, Event::Down, Event::Left, Event::Right,
+            Event::Up2, Event::Down2, Event::Left2, Event::Right2,
+            Event::Stop, Event::Stop2, Event::Select, Event::Back, Event::Pause,
+        ];
+        for &event in events.iter() {
+            assert_eq!(encode(event).and_then(decode), Some(event));
+        }
+        for &event in [Event::Tick, Event::Click(1, 2), Event::Quit, Event::Suspend].iter() {
+            assert_eq!(encode(event), None);
+        }
+        assert_eq!(decode('?'), None);
+    }
+
+    #[test]
+    fn both_sides_get_the_same_input_in_the_same_order() {
+        let ((mut host, _), (mut client, _)) = connect(Mode::Coop);
+        for _ in 0..3 {
+            host.send(&[Event::Up, Event::Select], 1).unwrap();
+            client.send(&[Event::Left2], 1).unwrap();
+            let expected = vec![Event::Up, Event::Select, Event::Left2];
+            assert_eq!(host.receive().unwrap(), Some(expected.clone()));
+            assert_eq!(client.receive().unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn different_checksums_are_a_desync() {
+        let ((mut host, _), (mut client, _)) = connect(Mode::Coop);
+        host.send(&[], 1).unwrap();
+        client.send(&[], 2).unwrap();
+
+        let error = host.receive().err().unwrap();
+        assert_eq!(error.to_string(), "desync detected at tick 0");
+        assert!(client.receive().is_err());
+    }
+
+    #[test]
+    fn clicks_stay_on_their_side() {
+        let ((mut host, _), (mut client, _)) = connect(Mode::Coop);
+
+        assert_eq!(host.local_event(Event::Click(3, 4)), None);
+        assert_eq!(client.local_event(Event::Click(3, 4)), None);
+        assert_eq!(client.local_event(Event::Left), Some(Event::Left2));
+
+        // Even if one gets through, neither side applies it
+        host.send(&[Event::Click(3, 4), Event::Right], 7).unwrap();
+        client.send(&[Event::Left2, Event::Click(5, 6)], 7).unwrap();
+        let expected = vec![Event::Right, Event::Left2];
+        assert_eq!(host.receive().unwrap(), Some(expected.clone()));
+        assert_eq!(client.receive().unwrap(), Some(expected));
+    }
+}
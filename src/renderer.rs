@@ -1,10 +1,133 @@
-#[derive(Copy, Clone)]
+//! Drawing, as far as the game is concerned: cells with a character and two
+//! colours on a grid.
+
+use std::io;
+
+/// Terminal colours. The sixteen named ones look however the terminal's
+/// palette has them.
+// The named colours say what they are
+#[allow(missing_docs)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Color {
-    White,
-    Cyan,
+    /// Whatever the terminal uses by default
+    Reset,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
     Magenta,
+    Cyan,
+    White,
+    LightBlack,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightWhite,
+    /// One of the 256 colours of the extended palette
+    Ansi(u8),
+    /// 24-bit truecolor
+    Rgb(u8, u8, u8),
 }
 
+impl Color {
+    /// The basic 16 colours, in palette order
+    pub const NAMED: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+        Color::LightBlack,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::LightWhite,
+    ];
+
+    /// Position in the 256-colour palette, or None for Reset and truecolor
+    pub fn palette_index(self) -> Option<u8> {
+        match self {
+            Color::Ansi(index) => Some(index),
+            Color::Reset | Color::Rgb(..) => None,
+            named => Color::NAMED.iter().position(|&c| c == named).map(|i| i as u8),
+        }
+    }
+}
+
+/// Something the game can draw on
 pub trait Renderer {
-    fn put_cell(&mut self, x: u16, y: u16, c: char, color: Color);
+    /// Set the cell at column `x` and row `y`, counted from 0. Cells off the
+    /// edge are left out.
+    fn put_cell(&mut self, x: u16, y: u16, c: char, fg: Color, bg: Color);
+}
+
+/// A renderer that draws one full frame at a time
+pub trait Surface: Renderer {
+    /// Start a new frame
+    fn clear(&mut self);
+    /// Show the frame drawn since `clear`
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Give the terminal back to the shell for a while
+    fn suspend(&mut self) {}
+
+    /// Take the terminal over again after `suspend`
+    fn resume(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects cells as coloured pixels and packs two rows of them into each
+/// terminal row, using the foreground for the top half and the background
+/// for the bottom half
+pub struct HalfBlocks {
+    pixels: Vec<Vec<Color>>,
+}
+
+impl HalfBlocks {
+    /// Size in pixels, which takes half as many rows on the terminal
+    pub fn new(width: usize, height: usize) -> HalfBlocks {
+        HalfBlocks {
+            pixels: vec![vec![Color::Reset; width]; height],
+        }
+    }
+
+    /// Put the pixels on a renderer, from its top left corner
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        for (y, pair) in self.pixels.chunks(2).enumerate() {
+            for x in 0..pair[0].len() {
+                let top = pair[0][x];
+                let bottom = pair.get(1).map_or(Color::Reset, |row| row[x]);
+
+                if top == bottom {
+                    renderer.put_cell(x as u16, y as u16, ' ', top, top);
+                } else if top == Color::Reset {
+                    // The default foreground isn't the default background
+                    renderer.put_cell(x as u16, y as u16, '▄', bottom, top);
+                } else {
+                    renderer.put_cell(x as u16, y as u16, '▀', top, bottom);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for HalfBlocks {
+    /// Blank cells show their background, anything else its foreground
+    fn put_cell(&mut self, x: u16, y: u16, c: char, fg: Color, bg: Color) {
+        let color = if c == ' ' { bg } else { fg };
+        if let Some(pixel) = self.pixels.get_mut(y as usize).and_then(|row| row.get_mut(x as usize)) {
+            *pixel = color;
+        }
+    }
 }
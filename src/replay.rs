@@ -0,0 +1,321 @@
+//! Games recorded with `--record`, for `retronix replay`.
+//!
+//! The same options, seed and input on the same ticks play out the same
+//! game, so that's all a replay holds, along with what else the game
+//! depends on: the theme and terminal it starts with (whether the tall
+//! board can be picked) and the level layouts, which have to be the same
+//! when playing it back.
+//!
+//! ```text
+//! retronix-replay 2
+//! level 1
+//! lives 3
+//! seed 8416207338290571873
+//! width 80
+//! height 25
+//! speed 1
+//! theme high contrast
+//! color-blocks 1
+//! layout 3 9f6e0c2d81a4b7e5
+//! 12 R
+//! 40 D S C31,16
+//! end 1200
+//! ```
+//!
+//! After the header comes a line for every tick with input, the events in
+//! the order they came in, and finally the tick the game ended on. Keys use
+//! the same letters as network play.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use game::{Event, PlayOptions, Settings};
+use levels::Layout;
+use net;
+use theme::Theme;
+
+const MAGIC: &str = "retronix-replay";
+const VERSION: u32 = 2;
+
+/// Levels that can have a layout drawn for them
+const LAYOUT_LEVELS: ::std::ops::RangeInclusive<u32> = 1..=99;
+
+pub struct Replay {
+    /// Always with a seed
+    pub options: PlayOptions,
+    pub speed: f64,
+    /// Name of the theme the game started with
+    pub theme: String,
+    /// Whether the terminal could show the tall board
+    pub color_blocks: bool,
+    /// Fingerprints of the levels that had a layout
+    layouts: BTreeMap<u32, u64>,
+    events: BTreeMap<u32, Vec<Event>>,
+    /// Ticks the game went on for
+    pub length: u32,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        match lines.next() {
+            Some(Ok(ref line)) if *line == format!("{} {}", MAGIC, VERSION) => {},
+            Some(Err(e)) => return Err(e),
+            _ => return Err(invalid("not a replay, or from another version".to_string())),
+        }
+
+        let mut replay = Replay {
+            options: PlayOptions::default(),
+            speed: 1.0,
+            theme: Theme::classic().name,
+            color_blocks: true,
+            layouts: BTreeMap::new(),
+            events: BTreeMap::new(),
+            length: 0,
+        };
+        let mut ended = false;
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            replay.parse_line(&line)
+                .map_err(|e| invalid(format!("line {}: {}", number + 2, e)))?;
+            ended |= line.starts_with("end ");
+        }
+
+        if replay.options.seed.is_none() {
+            return Err(invalid("no seed".to_string()));
+        }
+        replay.options.check().map_err(invalid)?;
+        // Cut short, play what there is
+        if !ended {
+            replay.length = replay.events.keys().next_back().map_or(0, |&tick| tick + 1);
+        }
+        Ok(replay)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let key = match words.next() {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let value = words.next().unwrap_or("");
+        let number = |value: &str| value.parse::<u32>().map_err(|_| format!("bad number {:?}", value));
+
+        match key {
+            "level" => self.options.level = number(value)?,
+            "lives" => self.options.lives = number(value)?,
+            "seed" => self.options.seed = Some(value.parse().map_err(|_| format!("bad seed {:?}", value))?),
+            "width" => self.options.width = number(value)? as usize,
+            "height" => self.options.height = number(value)? as usize,
+            "speed" => self.speed = value.parse().map_err(|_| format!("bad speed {:?}", value))?,
+            // The name may have spaces in it
+            "theme" => self.theme = line.trim_start()[key.len()..].trim().to_string(),
+            "color-blocks" => self.color_blocks = number(value)? != 0,
+            "layout" => {
+                let fingerprint = words.next().unwrap_or("");
+                let fingerprint = u64::from_str_radix(fingerprint, 16)
+                    .map_err(|_| format!("bad layout {:?}", fingerprint))?;
+                self.layouts.insert(number(value)?, fingerprint);
+            },
+            "end" => self.length = number(value)?,
+            tick => {
+                let events = line.split_whitespace().skip(1)
+                    .map(|token| decode(token).ok_or_else(|| format!("bad event {:?}", token)))
+                    .collect::<Result<Vec<Event>, String>>()?;
+                self.events.insert(number(tick)?, events);
+            },
+        }
+        Ok(())
+    }
+
+    /// The theme the game started with, if there is one by that name here
+    pub fn find_theme(&self) -> io::Result<Theme> {
+        Theme::available().into_iter()
+            .find(|theme| theme.name == self.theme)
+            .ok_or_else(|| invalid(format!("recorded with the {} theme, which isn't here", self.theme)))
+    }
+
+    /// Fails unless the levels have the same layouts here as when the game
+    /// was recorded
+    pub fn check_layouts(&self) -> io::Result<()> {
+        if layout_fingerprints() == self.layouts {
+            Ok(())
+        } else {
+            Err(invalid("recorded with other level layouts than the ones here".to_string()))
+        }
+    }
+
+    /// Input on the given tick, to be handled before it
+    pub fn events(&self, tick: u32) -> &[Event] {
+        self.events.get(&tick).map_or(&[], |events| events.as_slice())
+    }
+}
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    tick: u32,
+    pending: Vec<String>,
+}
+
+impl Recorder {
+    /// `options` must have a seed, `settings` are the ones the game starts
+    /// with
+    pub fn create(path: &Path,
+                  options: &PlayOptions,
+                  speed: f64,
+                  settings: &Settings) -> io::Result<Recorder> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{} {}", MAGIC, VERSION)?;
+        writeln!(file, "level {}", options.level)?;
+        writeln!(file, "lives {}", options.lives)?;
+        writeln!(file, "seed {}", options.seed.unwrap())?;
+        writeln!(file, "width {}", options.width)?;
+        writeln!(file, "height {}", options.height)?;
+        writeln!(file, "speed {}", speed)?;
+        writeln!(file, "theme {}", settings.theme.name)?;
+        writeln!(file, "color-blocks {}", settings.color_blocks as u8)?;
+        for (level, fingerprint) in layout_fingerprints() {
+            writeln!(file, "layout {} {:016x}", level, fingerprint)?;
+        }
+
+        Ok(Recorder {
+            file,
+            tick: 0,
+            pending: vec![],
+        })
+    }
+
+    /// Keep input the game was given, things it doesn't replay are left out
+    pub fn push(&mut self, event: Event) {
+        if let Some(token) = encode(event) {
+            self.pending.push(token);
+        }
+    }
+
+    /// Write out the input since the last tick
+    pub fn tick(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            writeln!(self.file, "{} {}", self.tick, self.pending.join(" "))?;
+            self.pending.clear();
+        }
+        self.tick += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        writeln!(self.file, "end {}", self.tick)?;
+        self.file.flush()
+    }
+}
+
+fn layout_fingerprints() -> BTreeMap<u32, u64> {
+    LAYOUT_LEVELS
+        .filter_map(|level| Layout::load(level).map(|layout| (level, layout.fingerprint())))
+        .collect()
+}
+
+fn encode(event: Event) -> Option<String> {
+    match event {
+        Event::Click(x, y) => Some(format!("C{},{}", x, y)),
+        event => net::encode(event).map(|c| c.to_string()),
+    }
+}
+
+fn decode(token: &str) -> Option<Event> {
+    if let Some(position) = token.strip_prefix('C') {
+        let mut parts = position.splitn(2, ',');
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        return Some(Event::Click(x, y));
+    }
+
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => net::decode(c),
+        _ => None,
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use game::{Event, PlayOptions, Settings};
+    use theme::Theme;
+    use super::{Recorder, Replay};
+
+    /// A file of its own for each test, as they run at the same time
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("retronix-{}-{}.replay", name, ::std::process::id()))
+    }
+
+    #[test]
+    fn recorded_game_loads_back() {
+        let path = temp_path("round-trip");
+        let options = PlayOptions { level: 4, lives: 2, seed: Some(1234), width: 100, height: 30 };
+        let settings = Settings { theme: Theme::high_contrast(), color_blocks: false, ..Settings::default() };
+
+        let mut recorder = Recorder::create(&path, &options, 1.5, &settings).unwrap();
+        recorder.push(Event::Select);
+        recorder.tick().unwrap();
+        recorder.tick().unwrap();
+        recorder.push(Event::Right);
+        recorder.push(Event::Click(31, 16));
+        // Not replayed
+        recorder.push(Event::Tick);
+        recorder.tick().unwrap();
+        recorder.finish().unwrap();
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let loaded = replay.options;
+        assert_eq!((loaded.level, loaded.lives, loaded.seed), (4, 2, Some(1234)));
+        assert_eq!((loaded.width, loaded.height), (100, 30));
+        assert_eq!(replay.speed, 1.5);
+        assert_eq!(replay.theme, Theme::high_contrast().name);
+        assert!(!replay.color_blocks);
+        assert_eq!(replay.events(0), &[Event::Select]);
+        assert_eq!(replay.events(1), &[]);
+        assert_eq!(replay.events(2), &[Event::Right, Event::Click(31, 16)]);
+        assert_eq!(replay.length, 3);
+        replay.check_layouts().unwrap();
+    }
+
+    #[test]
+    fn broken_replays_are_refused() {
+        let path = temp_path("broken");
+        let cases = [
+            "retronix-replay 1\nseed 1\n",
+            "retronix-replay 2\nlevel 1\n",
+            "retronix-replay 2\nseed 1\n12 X\n",
+            "retronix-replay 2\nseed 1\nlayout 3 nothex\n",
+            "retronix-replay 2\nseed 1\nwidth 5\n",
+        ];
+        for contents in cases.iter() {
+            fs::write(&path, contents).unwrap();
+            assert!(Replay::load(&path).is_err(), "{:?} should be refused", contents);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cut_short_replays_end_after_the_last_input() {
+        let path = temp_path("cut-short");
+        fs::write(&path, "retronix-replay 2\nseed 1\ntheme high contrast\n5 U L\n").unwrap();
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(replay.events(5), &[Event::Up, Event::Left]);
+        assert_eq!(replay.length, 6);
+        assert_eq!(replay.theme, "high contrast");
+    }
+}
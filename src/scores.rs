@@ -0,0 +1,98 @@
+//! High scores, kept in `$XDG_DATA_HOME/retronix/scores` (or
+//! `~/.local/share/retronix/scores`) as one `score level` pair per line,
+//! best first.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use dirs;
+
+const MAX_ENTRIES: usize = 10;
+
+/// One line of the table
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Entry {
+    /// Points scored, by all players of the game together
+    pub score: u32,
+    /// Level the game ended on
+    pub level: u32,
+}
+
+/// The best scores so far
+#[derive(Default)]
+pub struct HighScores {
+    entries: Vec<Entry>,
+}
+
+impl HighScores {
+    /// A missing or unreadable file is just an empty table
+    pub fn load() -> HighScores {
+        let contents = scores_path().and_then(|path| fs::read_to_string(path).ok());
+        let mut entries: Vec<Entry> = contents.iter()
+            .flat_map(|contents| contents.lines())
+            .filter_map(parse_entry)
+            .collect();
+        entries.sort_by_key(|entry| Reverse(entry.score));
+        entries.truncate(MAX_ENTRIES);
+
+        HighScores { entries }
+    }
+
+    /// Best first
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The top score, 0 when there's none yet
+    pub fn best(&self) -> u32 {
+        self.entries.first().map_or(0, |entry| entry.score)
+    }
+
+    /// Returns false if the score wasn't good enough for the table
+    pub fn record(&mut self, entry: Entry) -> bool {
+        if entry.score == 0 {
+            return false;
+        }
+
+        let position = self.entries.iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if position >= MAX_ENTRIES {
+            return false;
+        }
+
+        self.entries.insert(position, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        true
+    }
+
+    /// Write the table back to its file
+    pub fn save(&self) -> io::Result<()> {
+        let path = scores_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents: String = self.entries.iter()
+            .map(|entry| format!("{} {}\n", entry.score, entry.level))
+            .collect();
+        fs::write(path, contents)
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    match parts.as_slice() {
+        [score, level] => Some(Entry {
+            score: score.parse().ok()?,
+            level: level.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+fn scores_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("scores"))
+}
@@ -1,74 +1,89 @@
-extern crate termion;
+use std::io;
+use renderer::{Renderer, Surface, Color};
+use backend::{Backend, Terminal};
+use capabilities::Capabilities;
 
-use termion::raw::IntoRawMode;
-use termion::screen::AlternateScreen;
-use std::io::{self, Write};
-use std::fmt;
-use renderer::{Renderer, Color};
+pub fn init(width: usize, height: usize, capabilities: &Capabilities) -> io::Result<Screen> {
+    Ok(Screen {
+        terminal: Backend::from_env().open(capabilities)?,
+        buffer: vec![vec![BLANK; width]; height],
+        unicode: capabilities.unicode,
+        color: capabilities.color,
+    })
+}
 
-pub fn init(width: usize, height: usize) -> Screen {
-    let mut stdout = AlternateScreen::from(io::stdout().into_raw_mode().unwrap());
-    write!(stdout, "{}{}",
-           termion::clear::All,
-           termion::cursor::Hide).unwrap();
-    stdout.flush().unwrap();
+type Cell = (char, Color, Color);
 
-    Screen {
-        stdout,
-        buffer: vec![vec![(' ', Color::White); width]; height],
-    }
-}
+const BLANK: Cell = (' ', Color::White, Color::Reset);
 
 pub struct Screen {
-    stdout: termion::screen::AlternateScreen<termion::raw::RawTerminal<io::Stdout>>,
-    buffer: Vec<Vec<(char, Color)>>,
+    terminal: Box<dyn Terminal>,
+    buffer: Vec<Vec<Cell>>,
+    unicode: bool,
+    color: bool,
 }
 
-impl Screen {
-    pub fn clear(&mut self) {
+impl Surface for Screen {
+    fn clear(&mut self) {
         for row in self.buffer.iter_mut() {
             for cell in row.iter_mut() {
-                *cell = (' ', Color::White);
+                *cell = BLANK;
             }
         }
     }
 
-    pub fn flush(&mut self) {
-        let (cols, rows) = termion::terminal_size().unwrap();
-        write!(self.stdout, "{}", termion::cursor::Goto(1, 1)).unwrap();
-        for row in self.buffer.iter().take((rows - 1) as usize) {
-            for &(c, color) in row.iter().take(cols as usize) {
-                write!(self.stdout, "{}{}",
-                       color,
-                       c).unwrap();
+    fn flush(&mut self) -> io::Result<()> {
+        let (cols, rows) = self.terminal.size()?;
+        for (y, row) in self.buffer.iter().take(rows.saturating_sub(1) as usize).enumerate() {
+            self.terminal.move_to(0, y as u16)?;
+            for &(c, fg, bg) in row.iter().take(cols as usize) {
+                let c = if self.unicode { c } else { to_ascii(c) };
+                if self.color {
+                    self.terminal.set_colors(fg, bg)?;
+                }
+                self.terminal.print(c)?;
+            }
+            if self.color {
+                self.terminal.set_colors(Color::Reset, Color::Reset)?;
             }
-            write!(self.stdout, "\n\r").unwrap();
         }
-        self.stdout.flush().unwrap();
+        self.terminal.flush()
+    }
+
+    fn suspend(&mut self) {
+        self.terminal.suspend();
     }
-}
 
-impl fmt::Display for Color {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let termion_color = match *self {
-            Color::White => format!("{}", termion::color::Fg(termion::color::White)),
-            Color::Cyan => format!("{}", termion::color::Fg(termion::color::Cyan)),
-            Color::Magenta => format!("{}", termion::color::Fg(termion::color::Magenta)),
-        };
-        write!(f, "{}", termion_color)
+    fn resume(&mut self) -> io::Result<()> {
+        self.terminal.resume()
     }
 }
 
-impl Drop for Screen {
-    fn drop(&mut self) {
-        write!(self.stdout, "{}", termion::cursor::Show).unwrap();
-        self.stdout.flush().unwrap();
+/// Last resort for terminals without Unicode, when the theme isn't plain ASCII
+fn to_ascii(c: char) -> char {
+    match c {
+        c if c.is_ascii() => c,
+        '┌' | '┐' | '└' | '┘' | '╔' | '╗' | '╚' | '╝' => '+',
+        '─' => '-',
+        '═' => '=',
+        '│' | '║' => '|',
+        '░' => '.',
+        '▒' => ':',
+        '▓' => '%',
+        '█' => '#',
+        '●' | '◐' | '◓' | '◑' | '◒' => 'o',
+        '◆' | '◇' => '*',
+        '◢' | '◣' | '◤' | '◥' => 'x',
+        'Ł' => 'L',
+        _ => '?',
     }
 }
 
 impl Renderer for Screen {
-    fn put_cell(&mut self, x: u16, y: u16, c: char, color: Color) {
-        self.buffer[y as usize][x as usize] = (c, color);
+    fn put_cell(&mut self, x: u16, y: u16, c: char, fg: Color, bg: Color) {
+        if let Some(cell) = self.buffer.get_mut(y as usize).and_then(|row| row.get_mut(x as usize)) {
+            *cell = (c, fg, bg);
+        }
     }
 }
 
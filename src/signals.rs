@@ -0,0 +1,35 @@
+//! Signals from the shell, turned into events for the main loop.
+//!
+//! In raw mode Ctrl-C and Ctrl-Z arrive as keys, so these only come from
+//! outside: `kill`, a closing session or the shell continuing a stopped job.
+
+use signal_hook::consts::{SIGCONT, SIGTERM, SIGTSTP};
+use signal_hook::iterator::Signals;
+use signal_hook::low_level;
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+use game::Event;
+
+pub fn spawn(tx: mpsc::Sender<Event>) -> io::Result<()> {
+    let mut signals = Signals::new([SIGTSTP, SIGCONT, SIGTERM])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let event = match signal {
+                SIGTSTP => Event::Suspend,
+                SIGCONT => Event::Resume,
+                _ => Event::Terminate,
+            };
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stop the process the way SIGTSTP would have, returning once continued
+pub fn stop() {
+    let _ = low_level::emulate_default_handler(SIGTSTP);
+}
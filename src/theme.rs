@@ -0,0 +1,562 @@
+//! Glyphs and colours used to draw the game.
+//!
+//! Besides the built-in themes, any `*.theme` file found in
+//! `$XDG_CONFIG_HOME/retronix/themes` (or `~/.config/retronix/themes`)
+//! can be selected. A theme file starts from the classic theme and
+//! overrides elements one per line:
+//!
+//! ```text
+//! # comment
+//! name = Solarized
+//! land = █ #268bd2 #268bd2
+//! sea = space black
+//! border = ┌┐└┘─│ white black
+//! ```
+//!
+//! Each style is a glyph (or `space`), a foreground and an optional
+//! background colour. Colours are one of the 16 names (`cyan`,
+//! `light-cyan`, ...), `default`, a palette index `0`-`255` or `#rrggbb`.
+//! Enemies may list several glyphs (`sea_enemy = ◐◓◑◒ light-red black`),
+//! which are played in turn as an animation.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use dirs;
+use renderer::{Color, Renderer};
+
+/// How to draw one cell
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Style {
+    /// The character shown
+    pub glyph: char,
+    /// Colour of the character
+    pub fg: Color,
+    /// Colour behind it
+    pub bg: Color,
+}
+
+impl Style {
+    /// Same as the struct literal, only shorter
+    pub fn new(glyph: char, fg: Color, bg: Color) -> Style {
+        Style { glyph, fg, bg }
+    }
+
+    /// Same colours, different glyph
+    pub fn with_glyph(self, glyph: char) -> Style {
+        Style { glyph, ..self }
+    }
+}
+
+/// Where a box goes on the screen, for writing lines of text in it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Panel {
+    /// Column of the top left corner
+    pub x: u16,
+    /// Row of the top left corner
+    pub y: u16,
+    /// Columns, the border included
+    pub width: u16,
+    /// Rows, the border included
+    pub height: u16,
+}
+
+impl Panel {
+    /// A panel with its top left corner at `x`, `y`
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Panel {
+        Panel { x, y, width, height }
+    }
+
+    /// A panel in the middle of an area `(columns, rows)` wide and high,
+    /// such as the board
+    pub fn centred(width: u16, height: u16, area: (u16, u16)) -> Panel {
+        let (columns, rows) = area;
+        Panel::new(columns.saturating_sub(width) / 2, rows.saturating_sub(height) / 2, width, height)
+    }
+
+    /// Draw the box in the given style, blanking the inside
+    pub fn draw(&self, renderer: &mut dyn Renderer, style: &BoxStyle) {
+        style.draw(renderer, self.x, self.y, self.width, self.height);
+    }
+
+    /// Write `text` from `x` columns and `y` rows off the top left corner
+    pub fn text(&self, renderer: &mut dyn Renderer, x: u16, y: u16, text: &str, fg: Color, bg: Color) {
+        for (i, c) in text.chars().enumerate() {
+            renderer.put_cell(self.x + x + i as u16, self.y + y, c, fg, bg);
+        }
+    }
+
+    /// Write `text` in the middle of row `y`
+    pub fn centred_text(&self, renderer: &mut dyn Renderer, y: u16, text: &str, fg: Color, bg: Color) {
+        let x = self.width.saturating_sub(text.chars().count() as u16) / 2;
+        self.text(renderer, x, y, text, fg, bg);
+    }
+}
+
+/// Number of ticks each frame of a sprite is shown for
+const FRAME_TICKS: u32 = 4;
+
+/// A style whose glyph cycles through animation frames
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sprite {
+    /// Glyphs shown one after another
+    pub frames: Vec<char>,
+    /// Colour of the glyphs
+    pub fg: Color,
+    /// Colour behind them
+    pub bg: Color,
+}
+
+impl Sprite {
+    /// Every character of `frames` is a frame
+    pub fn new(frames: &str, fg: Color, bg: Color) -> Sprite {
+        Sprite { frames: frames.chars().collect(), fg, bg }
+    }
+
+    /// The frame to show after `ticks` ticks
+    pub fn frame(&self, ticks: u32) -> Style {
+        let index = (ticks / FRAME_TICKS) as usize % self.frames.len();
+        Style::new(self.frames[index], self.fg, self.bg)
+    }
+}
+
+/// Box drawing characters in the order ┌ ┐ └ ┘ ─ │, plus their colours
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoxStyle {
+    /// Corners, then the horizontal and the vertical line
+    pub chars: [char; 6],
+    /// Colour of the lines
+    pub fg: Color,
+    /// Colour behind them, and inside the box
+    pub bg: Color,
+}
+
+impl BoxStyle {
+    /// The character at `index` of `chars`, with the box's colours
+    pub fn style(&self, index: usize) -> Style {
+        Style::new(self.chars[index], self.fg, self.bg)
+    }
+
+    /// Draw a box with its top left corner at `x`, `y`, blanking the inside
+    pub fn draw(&self, renderer: &mut dyn Renderer, x: u16, y: u16, width: u16, height: u16) {
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        let mut put = |x, y, i: usize| renderer.put_cell(x, y, self.chars[i], self.fg, self.bg);
+
+        put(x, y, 0);
+        put(right, y, 1);
+        put(x, bottom, 2);
+        put(right, bottom, 3);
+        for cx in x + 1..right {
+            put(cx, y, 4);
+            put(cx, bottom, 4);
+        }
+        for cy in y + 1..bottom {
+            put(x, cy, 5);
+            put(right, cy, 5);
+        }
+
+        for cy in y + 1..bottom {
+            for cx in x + 1..right {
+                renderer.put_cell(cx, cy, ' ', self.fg, self.bg);
+            }
+        }
+    }
+}
+
+const SINGLE_BOX: [char; 6] = ['┌', '┐', '└', '┘', '─', '│'];
+const DOUBLE_BOX: [char; 6] = ['╔', '╗', '╚', '╝', '═', '║'];
+const LOGO_SHADES: [char; 3] = ['░', '▒', '█'];
+
+/// Everything the game draws, one style for each kind of thing
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// Shown under OPTIONS, the file name for theme files without one
+    pub name: String,
+    /// Filled fields
+    pub land: Style,
+    /// Fields still to fill
+    pub sea: Style,
+    /// Each player's trail on the sea
+    pub trail: [Style; 2],
+    /// Land each player claimed in versus mode
+    pub territory: [Style; 2],
+    /// The players themselves
+    pub player: [Style; 2],
+    /// Enemies bouncing around the sea
+    pub sea_enemy: Sprite,
+    /// Enemies roaming the land
+    pub land_enemy: Sprite,
+    /// Freeze, slow motion, extra life and shield
+    pub powerups: [Style; 4],
+    /// Text, like the status line and the menus
+    pub hud: Style,
+    /// The selected menu item, and the marker in front of it
+    pub highlight: Style,
+    /// The three shades the logo is made of
+    pub logo: [Style; 3],
+    /// Single line boxes
+    pub border: BoxStyle,
+    /// Double line boxes
+    pub frame: BoxStyle,
+}
+
+impl Theme {
+    /// The original colours
+    pub fn classic() -> Theme {
+        Theme {
+            name: "classic".to_string(),
+            land: Style::new('█', Color::Cyan, Color::Cyan),
+            sea: Style::new(' ', Color::Black, Color::Black),
+            trail: [
+                Style::new('▒', Color::Magenta, Color::Black),
+                Style::new('▒', Color::Yellow, Color::Black),
+            ],
+            territory: [
+                Style::new('▓', Color::Magenta, Color::Cyan),
+                Style::new('▓', Color::Yellow, Color::Cyan),
+            ],
+            player: [
+                Style::new('█', Color::LightMagenta, Color::LightMagenta),
+                Style::new('█', Color::LightYellow, Color::LightYellow),
+            ],
+            sea_enemy: Sprite::new("◐◓◑◒", Color::LightRed, Color::Black),
+            land_enemy: Sprite::new("◢◣◤◥", Color::Red, Color::Cyan),
+            powerups: [
+                Style::new('*', Color::LightWhite, Color::Black),
+                Style::new('~', Color::LightWhite, Color::Black),
+                Style::new('+', Color::LightWhite, Color::Black),
+                Style::new('o', Color::LightWhite, Color::Black),
+            ],
+            hud: Style::new(' ', Color::White, Color::Reset),
+            highlight: Style::new('>', Color::Magenta, Color::Reset),
+            logo: [
+                Style::new('░', Color::Magenta, Color::Reset),
+                Style::new('▒', Color::Cyan, Color::Reset),
+                Style::new('█', Color::White, Color::Reset),
+            ],
+            border: BoxStyle { chars: SINGLE_BOX, fg: Color::White, bg: Color::Reset },
+            frame: BoxStyle { chars: DOUBLE_BOX, fg: Color::White, bg: Color::Reset },
+        }
+    }
+
+    /// Bright colours on black
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high contrast".to_string(),
+            land: Style::new('█', Color::LightWhite, Color::LightWhite),
+            sea: Style::new(' ', Color::Black, Color::Black),
+            trail: [
+                Style::new('▒', Color::LightYellow, Color::Black),
+                Style::new('▒', Color::LightCyan, Color::Black),
+            ],
+            territory: [
+                Style::new('▓', Color::Yellow, Color::LightWhite),
+                Style::new('▓', Color::Blue, Color::LightWhite),
+            ],
+            player: [
+                Style::new('█', Color::LightRed, Color::LightRed),
+                Style::new('█', Color::LightBlue, Color::LightBlue),
+            ],
+            sea_enemy: Sprite::new("◐◓◑◒", Color::LightGreen, Color::Black),
+            land_enemy: Sprite::new("◆◇", Color::Black, Color::LightWhite),
+            powerups: [
+                Style::new('*', Color::LightYellow, Color::Black),
+                Style::new('~', Color::LightYellow, Color::Black),
+                Style::new('+', Color::LightYellow, Color::Black),
+                Style::new('o', Color::LightYellow, Color::Black),
+            ],
+            hud: Style::new(' ', Color::LightWhite, Color::Black),
+            highlight: Style::new('>', Color::LightYellow, Color::Black),
+            logo: [
+                Style::new('░', Color::LightWhite, Color::Black),
+                Style::new('▒', Color::LightWhite, Color::Black),
+                Style::new('█', Color::LightWhite, Color::Black),
+            ],
+            border: BoxStyle { chars: SINGLE_BOX, fg: Color::LightWhite, bg: Color::Black },
+            frame: BoxStyle { chars: DOUBLE_BOX, fg: Color::LightYellow, bg: Color::Black },
+        }
+    }
+
+    /// Built on the Okabe-Ito palette, tells players apart by shape as well
+    pub fn colourblind() -> Theme {
+        let orange = Color::Rgb(230, 159, 0);
+        let sky_blue = Color::Rgb(86, 180, 233);
+        let blue = Color::Rgb(0, 114, 178);
+        let yellow = Color::Rgb(240, 228, 66);
+        let vermillion = Color::Rgb(213, 94, 0);
+
+        Theme {
+            name: "colourblind-safe".to_string(),
+            land: Style::new('█', blue, blue),
+            sea: Style::new(' ', Color::Black, Color::Black),
+            trail: [
+                Style::new('▒', orange, Color::Black),
+                Style::new('░', sky_blue, Color::Black),
+            ],
+            territory: [
+                Style::new('▓', orange, blue),
+                Style::new('░', sky_blue, blue),
+            ],
+            player: [
+                Style::new('█', orange, orange),
+                Style::new('◆', sky_blue, Color::Black),
+            ],
+            sea_enemy: Sprite::new("◐◓◑◒", yellow, Color::Black),
+            land_enemy: Sprite::new("◆◇", vermillion, blue),
+            powerups: [
+                Style::new('*', Color::LightWhite, Color::Black),
+                Style::new('~', Color::LightWhite, Color::Black),
+                Style::new('+', Color::LightWhite, Color::Black),
+                Style::new('o', Color::LightWhite, Color::Black),
+            ],
+            hud: Style::new(' ', Color::LightWhite, Color::Reset),
+            highlight: Style::new('>', orange, Color::Reset),
+            logo: [
+                Style::new('░', orange, Color::Reset),
+                Style::new('▒', sky_blue, Color::Reset),
+                Style::new('█', Color::LightWhite, Color::Reset),
+            ],
+            border: BoxStyle { chars: SINGLE_BOX, fg: Color::LightWhite, bg: Color::Reset },
+            frame: BoxStyle { chars: DOUBLE_BOX, fg: Color::LightWhite, bg: Color::Reset },
+        }
+    }
+
+    /// Plain 7-bit characters in the terminal's own colours
+    pub fn ascii() -> Theme {
+        let plain = |glyph| Style::new(glyph, Color::Reset, Color::Reset);
+
+        Theme {
+            name: "ASCII-only".to_string(),
+            land: plain('#'),
+            sea: plain(' '),
+            trail: [plain('.'), plain(',')],
+            territory: [plain('1'), plain('2')],
+            player: [plain('@'), plain('&')],
+            sea_enemy: Sprite::new("oO", Color::Reset, Color::Reset),
+            land_enemy: Sprite::new("x+", Color::Reset, Color::Reset),
+            powerups: [plain('*'), plain('~'), plain('+'), plain('$')],
+            hud: plain(' '),
+            highlight: plain('>'),
+            logo: [plain('.'), plain(':'), plain('#')],
+            border: BoxStyle { chars: ['+', '+', '+', '+', '-', '|'], fg: Color::Reset, bg: Color::Reset },
+            frame: BoxStyle { chars: ['+', '+', '+', '+', '=', '|'], fg: Color::Reset, bg: Color::Reset },
+        }
+    }
+
+    /// The themes that come with the game, classic first
+    pub fn builtin() -> Vec<Theme> {
+        vec![
+            Theme::classic(),
+            Theme::high_contrast(),
+            Theme::colourblind(),
+            Theme::ascii(),
+        ]
+    }
+
+    /// Built-in themes followed by the ones found in the themes directory
+    pub fn available() -> Vec<Theme> {
+        let mut themes = Theme::builtin();
+
+        let entries = theme_dir().and_then(|dir| fs::read_dir(dir).ok());
+        let mut paths: Vec<PathBuf> = entries.into_iter()
+            .flat_map(|entries| entries.filter_map(|e| e.ok()))
+            .map(|e| e.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "theme"))
+            .collect();
+        paths.sort();
+
+        // A broken theme file shouldn't keep the game from starting
+        themes.extend(paths.iter().filter_map(|path| Theme::load(path).ok()));
+        themes
+    }
+
+    /// The available theme with the given name, ignoring case, spaces and
+    /// dashes, so `ascii` or `highcontrast` are enough
+    pub fn find(name: &str) -> Option<Theme> {
+        let simplify = |name: &str| -> String {
+            name.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+        };
+        let name = simplify(name);
+        if name.is_empty() {
+            return None;
+        }
+
+        // An exact match wins over a theme that only starts the same
+        let themes = Theme::available();
+        let exact = themes.iter().position(|theme| simplify(&theme.name) == name);
+        let prefix = || themes.iter().position(|theme| simplify(&theme.name).starts_with(&name));
+        let position = exact.or_else(prefix)?;
+        themes.into_iter().nth(position)
+    }
+
+    /// Read a theme file, see the top of this module for what goes in it
+    pub fn load(path: &Path) -> io::Result<Theme> {
+        let mut theme = Theme::classic();
+        theme.name = path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let contents = fs::read_to_string(path)?;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            theme.apply(line).map_err(|message| {
+                io::Error::new(io::ErrorKind::InvalidData,
+                               format!("{}:{}: {}", path.display(), number + 1, message))
+            })?;
+        }
+
+        Ok(theme)
+    }
+
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().ok_or("expected `element = value`")?.trim();
+
+        match key {
+            "name" => self.name = value.to_string(),
+            "border" => self.border = parse_box(value)?,
+            "frame" => self.frame = parse_box(value)?,
+            "sea_enemy" => self.sea_enemy = parse_sprite(value)?,
+            "land_enemy" => self.land_enemy = parse_sprite(value)?,
+            key => *self.style_mut(key).ok_or_else(|| format!("unknown element `{}`", key))? =
+                parse_style(value)?,
+        }
+        Ok(())
+    }
+
+    fn style_mut(&mut self, key: &str) -> Option<&mut Style> {
+        let style = match key {
+            "land" => &mut self.land,
+            "sea" => &mut self.sea,
+            "trail.1" => &mut self.trail[0],
+            "trail.2" => &mut self.trail[1],
+            "territory.1" => &mut self.territory[0],
+            "territory.2" => &mut self.territory[1],
+            "player.1" => &mut self.player[0],
+            "player.2" => &mut self.player[1],
+            "powerup.freeze" => &mut self.powerups[0],
+            "powerup.slow" => &mut self.powerups[1],
+            "powerup.life" => &mut self.powerups[2],
+            "powerup.shield" => &mut self.powerups[3],
+            "hud" => &mut self.hud,
+            "highlight" => &mut self.highlight,
+            "logo.1" => &mut self.logo[0],
+            "logo.2" => &mut self.logo[1],
+            "logo.3" => &mut self.logo[2],
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    /// Land and sea differ in colour, not just in glyph. The tall board
+    /// needs that, as it draws the colour of each field and nothing else.
+    pub fn has_colors(&self) -> bool {
+        // What `HalfBlocks` takes a cell's colour to be
+        let color = |style: &Style| if style.glyph == ' ' { style.bg } else { style.fg };
+        color(&self.land) != color(&self.sea)
+    }
+
+    /// Style for a character of the built-in artwork: logo shades and box
+    /// drawing are themed, anything else is text in the HUD style
+    pub fn translate(&self, c: char) -> Style {
+        if let Some(i) = LOGO_SHADES.iter().position(|&s| s == c) {
+            return self.logo[i];
+        }
+        if let Some(i) = SINGLE_BOX.iter().position(|&s| s == c) {
+            return self.border.style(i);
+        }
+        if let Some(i) = DOUBLE_BOX.iter().position(|&s| s == c) {
+            return self.frame.style(i);
+        }
+        self.hud.with_glyph(c)
+    }
+}
+
+fn theme_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("themes"))
+}
+
+fn parse_style(value: &str) -> Result<Style, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (glyph, fg, bg) = match parts.as_slice() {
+        [glyph, fg] => (glyph, fg, &"default"),
+        [glyph, fg, bg] => (glyph, fg, bg),
+        _ => return Err("expected `glyph fg [bg]`".to_string()),
+    };
+
+    let glyph = match *glyph {
+        "space" => ' ',
+        glyph => {
+            let mut chars = glyph.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("`{}` is not a single glyph", glyph)),
+            }
+        },
+    };
+
+    Ok(Style::new(glyph, parse_color(fg)?, parse_color(bg)?))
+}
+
+fn parse_sprite(value: &str) -> Result<Sprite, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (frames, fg, bg) = match parts.as_slice() {
+        [frames, fg] => (frames, fg, &"default"),
+        [frames, fg, bg] => (frames, fg, bg),
+        _ => return Err("expected `frames fg [bg]`".to_string()),
+    };
+
+    let frames = if *frames == "space" { " " } else { frames };
+    Ok(Sprite::new(frames, parse_color(fg)?, parse_color(bg)?))
+}
+
+fn parse_box(value: &str) -> Result<BoxStyle, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (chars, fg, bg) = match parts.as_slice() {
+        [chars, fg] => (chars, fg, &"default"),
+        [chars, fg, bg] => (chars, fg, bg),
+        _ => return Err("expected `┌┐└┘─│ fg [bg]`".to_string()),
+    };
+
+    let chars: Vec<char> = chars.chars().collect();
+    if chars.len() != 6 {
+        return Err("a box needs exactly 6 characters".to_string());
+    }
+
+    Ok(BoxStyle {
+        chars: [chars[0], chars[1], chars[2], chars[3], chars[4], chars[5]],
+        fg: parse_color(fg)?,
+        bg: parse_color(bg)?,
+    })
+}
+
+const COLOR_NAMES: [&str; 16] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    "light-black", "light-red", "light-green", "light-yellow",
+    "light-blue", "light-magenta", "light-cyan", "light-white",
+];
+
+/// A colour as written in theme files
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    if value == "default" {
+        return Ok(Color::Reset);
+    }
+
+    if let Some(i) = COLOR_NAMES.iter().position(|&name| name == value) {
+        return Ok(Color::NAMED[i]);
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        return match u32::from_str_radix(hex, 16) {
+            Ok(rgb) if hex.len() == 6 => Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+            _ => Err(format!("invalid colour `{}`", value)),
+        };
+    }
+
+    value.parse().map(Color::Ansi).map_err(|_| format!("invalid colour `{}`", value))
+}